@@ -0,0 +1,322 @@
+//
+// Copyright (c) 2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use proc_macro2::Span;
+use syn::parse::Parse;
+use syn::{Attribute, Ident, Lit, Meta, NestedMeta, Path};
+
+/// The options that may be given in a `#[lens(...)]` attribute on the type itself.
+#[derive(Default)]
+pub(crate) struct ContainerOptions {
+    /// The module to declare the generated items in, if any (from `#[lens(module)]`, which uses
+    /// the default module name, or `#[lens(module = "name")]`).
+    pub module: Option<ModuleName>,
+
+    /// The path of the foreign type whose fields are restated by the type, if any (from
+    /// `#[lens(remote = "path::to::Type")]`).
+    pub remote: Option<Path>,
+
+    /// The module that declares a marker type for each field marked with `#[lens(field)]`, if
+    /// any (from `#[lens(fields = "path::to::markers")]`), in which case `HasField` is
+    /// implemented for each of those fields.
+    pub fields: Option<Path>,
+
+    /// Whether to generate `with_<field>` and `map_<field>` methods on the type (from
+    /// `#[lens(methods)]`).
+    pub methods: Option<Span>,
+
+    /// Whether to declare an associated constant on the type for the lens for each field (from
+    /// `#[lens(consts)]`).
+    pub consts: Option<Span>,
+}
+
+/// The options that may be given in a `#[lens(...)]` attribute on a field.
+#[derive(Default)]
+pub(crate) struct FieldOptions {
+    /// The type that restates the fields of the (foreign) field type, if any (from
+    /// `#[lens(remote = "TypeDef")]`).
+    pub remote: Option<Path>,
+
+    /// The methods that the lens for the field should go through instead of accessing the field
+    /// directly, if any (from `#[lens(get = "port", set = "set_port")]`).
+    pub accessors: Option<Accessors>,
+
+    /// Whether to implement `HasField` for the field, using the marker of the same name in the
+    /// module given by `#[lens(fields = "...")]` on the type (from `#[lens(field)]`).
+    pub field: Option<Span>,
+}
+
+/// The getter and setter methods given by `#[lens(get = "...", set = "...")]`.
+pub(crate) struct Accessors {
+    /// The getter method, which takes `&self` and returns the field value.
+    pub get: Ident,
+
+    /// The setter method, which takes `&mut self` and the new field value.
+    pub set: Ident,
+}
+
+/// The name of the module given by `#[lens(module)]`.
+pub(crate) enum ModuleName {
+    /// Use the default module name, which is derived from the type name.
+    Default(Span),
+
+    /// Use the given module name.
+    Explicit(Ident),
+}
+
+/// Parse the options from the `#[lens(...)]` attributes on the type, or return an error (spanned
+/// to the offending option) if any option is not recognized.
+pub(crate) fn parse_container_options(attrs: &[Attribute]) -> syn::Result<ContainerOptions> {
+    let mut options = ContainerOptions::default();
+    for nested in lens_attr_items(attrs)? {
+        match &nested {
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("module") => {
+                options.module = Some(ModuleName::Default(span_of(path)));
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("methods") => {
+                options.methods = Some(span_of(path));
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("consts") => {
+                options.consts = Some(span_of(path));
+            }
+            NestedMeta::Meta(Meta::NameValue(name_value)) if name_value.path.is_ident("module") => {
+                options.module = Some(ModuleName::Explicit(parse_str_lit(&name_value.lit)?));
+            }
+            NestedMeta::Meta(Meta::NameValue(name_value)) if name_value.path.is_ident("remote") => {
+                options.remote = Some(parse_str_lit(&name_value.lit)?);
+            }
+            NestedMeta::Meta(Meta::NameValue(name_value)) if name_value.path.is_ident("fields") => {
+                options.fields = Some(parse_str_lit(&name_value.lit)?);
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &nested,
+                    "unrecognized `#[lens(...)]` option",
+                ))
+            }
+        }
+    }
+    Ok(options)
+}
+
+/// Parse the options from the `#[lens(...)]` attributes on a field, or return an error (spanned
+/// to the offending option) if any option is not recognized.
+pub(crate) fn parse_field_options(attrs: &[Attribute]) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+    let mut get: Option<Ident> = None;
+    let mut set: Option<Ident> = None;
+    for nested in lens_attr_items(attrs)? {
+        match &nested {
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("field") => {
+                options.field = Some(span_of(path));
+            }
+            NestedMeta::Meta(Meta::NameValue(name_value)) if name_value.path.is_ident("remote") => {
+                options.remote = Some(parse_str_lit(&name_value.lit)?);
+            }
+            NestedMeta::Meta(Meta::NameValue(name_value)) if name_value.path.is_ident("get") => {
+                get = Some(parse_str_lit(&name_value.lit)?);
+            }
+            NestedMeta::Meta(Meta::NameValue(name_value)) if name_value.path.is_ident("set") => {
+                set = Some(parse_str_lit(&name_value.lit)?);
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &nested,
+                    "unrecognized `#[lens(...)]` option",
+                ))
+            }
+        }
+    }
+    // A lens needs both a way to get the field and a way to set it
+    options.accessors = match (get, set) {
+        (Some(get), Some(set)) => Some(Accessors { get, set }),
+        (Some(method), None) | (None, Some(method)) => {
+            return Err(syn::Error::new_spanned(
+                method,
+                "`#[lens(get = \"...\", set = \"...\")]` requires both a getter and a setter",
+            ))
+        }
+        (None, None) => None,
+    };
+    Ok(options)
+}
+
+/// Return the items of each `#[lens(...)]` attribute in the given list.
+fn lens_attr_items(attrs: &[Attribute]) -> syn::Result<Vec<NestedMeta>> {
+    let mut items = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("lens")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => items.extend(list.nested),
+            meta => {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    "expected `#[lens(...)]` with a list of options",
+                ))
+            }
+        }
+    }
+    Ok(items)
+}
+
+/// Parse the contents of the given string literal (for example, an identifier or a path), or
+/// return an error (spanned to the literal) if it is not a string containing a valid `T`.
+fn parse_str_lit<T: Parse>(lit: &Lit) -> syn::Result<T> {
+    match lit {
+        Lit::Str(s) => s.parse(),
+        _ => Err(syn::Error::new_spanned(lit, "expected a string literal")),
+    }
+}
+
+/// Return the span of the first segment of the given path.
+fn span_of(path: &syn::Path) -> Span {
+    path.segments[0].ident.span()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::{parse_quote, DeriveInput};
+
+    fn first_field(input: &DeriveInput) -> &syn::Field {
+        match &input.data {
+            syn::Data::Struct(data_struct) => data_struct.fields.iter().next().unwrap(),
+            _ => panic!("expected a struct"),
+        }
+    }
+
+    fn module_name(input: DeriveInput) -> Option<String> {
+        parse_container_options(&input.attrs)
+            .unwrap()
+            .module
+            .map(|name| match name {
+                ModuleName::Default(_) => "<default>".to_string(),
+                ModuleName::Explicit(ident) => ident.to_string(),
+            })
+    }
+
+    #[test]
+    fn the_module_option_should_be_parsed() {
+        let input = parse_quote!(
+            struct Person {}
+        );
+        assert_eq!(module_name(input), None);
+
+        let input = parse_quote!(
+            #[lens(module)]
+            struct Person {}
+        );
+        assert_eq!(module_name(input), Some("<default>".to_string()));
+
+        let input = parse_quote!(
+            #[lens(module = "people")]
+            struct Person {}
+        );
+        assert_eq!(module_name(input), Some("people".to_string()));
+    }
+
+    #[test]
+    fn the_remote_option_should_be_parsed() {
+        let input: DeriveInput = parse_quote!(
+            #[lens(remote = "std::time::Duration")]
+            struct DurationDef {
+                #[lens(remote = "OtherDef")]
+                other: Other,
+            }
+        );
+        let remote = parse_container_options(&input.attrs).unwrap().remote;
+        assert_eq!(
+            quote::quote!(#remote).to_string(),
+            quote::quote!(std::time::Duration).to_string()
+        );
+
+        let remote = parse_field_options(&first_field(&input).attrs)
+            .unwrap()
+            .remote;
+        assert_eq!(
+            quote::quote!(#remote).to_string(),
+            quote::quote!(OtherDef).to_string()
+        );
+    }
+
+    #[test]
+    fn accessors_should_require_both_a_getter_and_a_setter() {
+        let input = parse_quote!(
+            struct Server {
+                #[lens(get = "port", set = "set_port")]
+                port: u16,
+            }
+        );
+        let accessors = parse_field_options(&first_field(&input).attrs)
+            .unwrap()
+            .accessors
+            .unwrap();
+        assert_eq!(accessors.get, "port");
+        assert_eq!(accessors.set, "set_port");
+
+        let input = parse_quote!(
+            struct Server {
+                #[lens(get = "port")]
+                port: u16,
+            }
+        );
+        let error = parse_field_options(&first_field(&input).attrs)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "`#[lens(get = \"...\", set = \"...\")]` requires both a getter and a setter"
+        );
+    }
+
+    #[test]
+    fn the_field_option_should_be_parsed() {
+        let input = parse_quote!(
+            struct Document {
+                #[lens(field)]
+                updated_at: u64,
+            }
+        );
+        let options = parse_field_options(&first_field(&input).attrs).unwrap();
+        assert!(options.field.is_some());
+
+        let input = parse_quote!(
+            struct Document {
+                updated_at: u64,
+            }
+        );
+        let options = parse_field_options(&first_field(&input).attrs).unwrap();
+        assert!(options.field.is_none());
+    }
+
+    #[test]
+    fn the_consts_option_should_be_parsed() {
+        let input: DeriveInput = parse_quote!(
+            #[lens(consts)]
+            struct Person {}
+        );
+        assert!(parse_container_options(&input.attrs)
+            .unwrap()
+            .consts
+            .is_some());
+
+        let input: DeriveInput = parse_quote!(
+            struct Person {}
+        );
+        assert!(parse_container_options(&input.attrs)
+            .unwrap()
+            .consts
+            .is_none());
+    }
+
+    #[test]
+    fn unrecognized_options_should_be_rejected() {
+        let input: DeriveInput = parse_quote!(
+            #[lens(modul)]
+            struct Person {}
+        );
+        let error = parse_container_options(&input.attrs).err().unwrap();
+        assert_eq!(error.to_string(), "unrecognized `#[lens(...)]` option");
+    }
+}
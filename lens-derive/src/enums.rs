@@ -0,0 +1,606 @@
+//
+// Copyright (c) 2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{DataEnum, DeriveInput, Field, Fields, Variant};
+
+use crate::attrs::parse_field_options;
+use crate::{
+    check_no_struct_field_options, field_ident, field_lens_impls, lens_entry_impl,
+    replace_self_type, target_lenses_impl, to_camel_case, FieldAccess,
+};
+
+/// Generates a `Prism` for each unit or tuple variant in the given enum, and an `AffineLens` for
+/// each field of each struct variant.  Fields that are shared by every variant (i.e., that have
+/// the same name and type in each variant) also get a `Lens`.
+pub(crate) fn derive_enum_lenses(
+    input: &DeriveInput,
+    data_enum: &DataEnum,
+) -> syn::Result<TokenStream2> {
+    // Extract the enum name
+    let enum_name = &input.ident;
+
+    // The lens structs are declared alongside the enum, so they share its visibility
+    let lens_visibility = &input.vis;
+
+    // Generate the optics for each variant in the enum, along with an entry for each variant
+    // in the `<EnumName>Lenses` struct and instance (and a `HasFieldLens` impl, which is how
+    // `lens!` looks up the entry by name)
+    let mut variant_items = Vec::new();
+    let mut lenses_struct_fields = Vec::new();
+    let mut lenses_value_fields = Vec::new();
+    let lenses_struct_name = format_ident!("{}Lenses", enum_name);
+    let lenses_struct_type = quote!(#lenses_struct_name);
+    let no_generics = syn::Generics::default();
+    for variant in data_enum.variants.iter() {
+        let variant_name = &variant.ident;
+        match &variant.fields {
+            Fields::Named(_) => {
+                // Build an affine lens for each field in the struct variant, along with a
+                // `<EnumName>Variant<VariantName>Lenses` struct that enumerates those lenses
+                // (the `Variant` keeps these names apart from those generated for a struct
+                // named `<EnumName><VariantName>`)
+                let variant_lenses_struct_name =
+                    format_ident!("{}Variant{}Lenses", enum_name, variant_name);
+                let variant_lenses_struct_type = quote!(#variant_lenses_struct_name);
+                let mut variant_lenses_struct_fields = Vec::new();
+                let mut variant_lenses_value_fields = Vec::new();
+                for field in variant.fields.iter() {
+                    let field_name = field_ident(field)?;
+                    let lens_name = format_ident!(
+                        "{}Variant{}{}Lens",
+                        enum_name,
+                        variant_name,
+                        to_camel_case(&field_name.unraw().to_string())
+                    );
+                    variant_items.push(struct_variant_field_lens(
+                        input, variant, field, &lens_name,
+                    )?);
+                    variant_lenses_struct_fields
+                        .push(quote!(#lens_visibility #field_name: #lens_name));
+                    variant_lenses_value_fields.push(quote!(#field_name: #lens_name));
+                    variant_items.push(lens_entry_impl(
+                        &variant_lenses_struct_type,
+                        &no_generics,
+                        field_name,
+                        &quote!(#lens_name),
+                        quote!(pl_lens::AffineKind),
+                    ));
+                }
+                variant_items.push(quote!(
+                    #[allow(dead_code)]
+                    #[doc(hidden)]
+                    #lens_visibility struct #variant_lenses_struct_name {
+                        #(#variant_lenses_struct_fields),*
+                    }
+                ));
+                variant_items.push(struct_variant_optic(
+                    input,
+                    variant,
+                    &variant_lenses_struct_name,
+                    &variant_lenses_value_fields,
+                ));
+                variant_items.push(lens_entry_impl(
+                    &lenses_struct_type,
+                    &no_generics,
+                    variant_name,
+                    &variant_lenses_struct_type,
+                    quote!(pl_lens::AffineKind),
+                ));
+                lenses_struct_fields
+                    .push(quote!(#lens_visibility #variant_name: #variant_lenses_struct_name));
+                lenses_value_fields.push(quote!(
+                    #variant_name: #variant_lenses_struct_name {
+                        #(#variant_lenses_value_fields),*
+                    }
+                ));
+            }
+            Fields::Unnamed(_) | Fields::Unit => {
+                // Build a prism that focuses on the payload of the variant
+                let prism_name = format_ident!("{}{}Prism", enum_name, variant_name);
+                variant_items.push(variant_prism(input, variant, &prism_name)?);
+                variant_items.push(lens_entry_impl(
+                    &lenses_struct_type,
+                    &no_generics,
+                    variant_name,
+                    &quote!(#prism_name),
+                    quote!(pl_lens::AffineKind),
+                ));
+                lenses_struct_fields.push(quote!(#lens_visibility #variant_name: #prism_name));
+                lenses_value_fields.push(quote!(#variant_name: #prism_name));
+            }
+        }
+    }
+
+    // Generate a (total) lens for each field that is shared by every variant in the enum,
+    // along with an entry for each in the `<EnumName>Lenses` struct and instance
+    for (field_index, field) in shared_fields(data_enum) {
+        let field_name = field_ident(field)?;
+        let lens_name = format_ident!(
+            "{}{}Lens",
+            enum_name,
+            to_camel_case(&field_name.unraw().to_string())
+        );
+        variant_items.push(shared_field_lens(
+            input,
+            data_enum,
+            field,
+            field_index,
+            &lens_name,
+        )?);
+        variant_items.push(lens_entry_impl(
+            &lenses_struct_type,
+            &no_generics,
+            field_name,
+            &quote!(#lens_name),
+            quote!(pl_lens::LensKind),
+        ));
+        lenses_struct_fields.push(quote!(#lens_visibility #field_name: #lens_name));
+        lenses_value_fields.push(quote!(#field_name: #lens_name));
+    }
+
+    // Build a `<EnumName>Lenses` struct that enumerates the available optics for each
+    // variant (and each shared field) in the enum, for example:
+    //     struct ShapeLenses {
+    //         Circle: ShapeCirclePrism,
+    //         Rect: ShapeVariantRectLenses,
+    //         id: ShapeIdLens
+    //     }
+    let lenses_struct = quote!(
+        #[allow(dead_code)]
+        #[allow(non_snake_case)]
+        #[doc(hidden)]
+        #lens_visibility struct #lenses_struct_name {
+            #(#lenses_struct_fields),*
+        }
+    );
+
+    // Implement `HasLenses` for the enum so that the optics can be resolved from the enum
+    // type; the `<EnumName>Lenses` instance holds the available optics for each variant (and
+    // each shared field) in the enum, for example:
+    //     ShapeLenses {
+    //         Circle: ShapeCirclePrism,
+    //         Rect: ShapeVariantRectLenses {
+    //             id: ShapeVariantRectIdLens,
+    //             width: ShapeVariantRectWidthLens,
+    //             height: ShapeVariantRectHeightLens
+    //         },
+    //         id: ShapeIdLens
+    //     }
+    let has_lenses_impl = quote!(
+        #[allow(dead_code)]
+        impl pl_lens::HasLenses for #enum_name {
+            type Lenses = #lenses_struct_name;
+
+            #[inline(always)]
+            fn lenses() -> #lenses_struct_name {
+                #lenses_struct_name {
+                    #(#lenses_value_fields),*
+                }
+            }
+        }
+    );
+
+    // Build the output
+    Ok(quote! {
+        #(#variant_items)*
+
+        #lenses_struct
+
+        #has_lenses_impl
+    })
+}
+
+/// Generates a `Prism` for the given unit or tuple variant.  The prism targets `()` for a unit
+/// variant, the field type for a tuple variant with a single field, and a tuple of the field
+/// types for a tuple variant with multiple fields.
+fn variant_prism(
+    input: &DeriveInput,
+    variant: &Variant,
+    prism_name: &syn::Ident,
+) -> syn::Result<TokenStream2> {
+    let enum_name = &input.ident;
+    let lens_visibility = &input.vis;
+    let variant_name = &variant.ident;
+
+    // Build the target type along with the patterns/expressions used to destructure and
+    // rebuild the variant, for example (for a variant with two fields):
+    //     target_type:      (f64, f64)
+    //     variant_pattern:  Shape::Line(field0, field1)
+    //     target_pattern:   (value0, value1)
+    //     target_clone:     (field0.clone(), field1.clone())
+    let field_types: Vec<TokenStream2> = variant
+        .fields
+        .iter()
+        .map(|field| {
+            let field_type = &field.ty;
+            replace_self_type(quote!(#field_type), enum_name)
+        })
+        .collect();
+    let fields: Vec<syn::Ident> = (0..field_types.len())
+        .map(|index| format_ident!("field{}", index))
+        .collect();
+    let values: Vec<syn::Ident> = (0..field_types.len())
+        .map(|index| format_ident!("value{}", index))
+        .collect();
+    let variant_pattern = match &variant.fields {
+        Fields::Unit => quote!(#enum_name::#variant_name),
+        _ => quote!(#enum_name::#variant_name(#(#fields),*)),
+    };
+    let variant_expr = match &variant.fields {
+        Fields::Unit => quote!(#enum_name::#variant_name),
+        _ => quote!(#enum_name::#variant_name(#(#values),*)),
+    };
+    let (target_type, target_pattern, target_clone) = match field_types.len() {
+        0 => (quote!(()), quote!(_), quote!(())),
+        1 => (
+            quote!(#(#field_types)*),
+            quote!(#(#values)*),
+            quote!(#(#fields)*.clone()),
+        ),
+        _ => (
+            quote!((#(#field_types),*)),
+            quote!((#(#values),*)),
+            quote!((#(#fields.clone()),*)),
+        ),
+    };
+
+    // The target can only be accessed by reference (and so reached into by `lens!`) if it is
+    // a single field
+    let target_lenses = if field_types.len() == 1 {
+        let field_options = parse_field_options(&variant.fields.iter().next().unwrap().attrs)?;
+        check_no_struct_field_options(&field_options)?;
+        target_lenses_impl(prism_name, &input.generics, &field_options, &target_type)
+    } else {
+        quote!()
+    };
+    let ref_lens = if field_types.len() == 1 {
+        quote!(
+            #[allow(dead_code)]
+            impl pl_lens::AffineRefLens for #prism_name {
+                #[inline(always)]
+                #[allow(unreachable_patterns)]
+                fn try_get_ref<'a>(&self, source: &'a #enum_name) -> Option<&'a #target_type> {
+                    match source {
+                        #variant_pattern => Some(#(#fields)*),
+                        _ => None,
+                    }
+                }
+
+                #[inline(always)]
+                #[allow(unreachable_patterns)]
+                fn try_get_mut_ref<'a>(&self, source: &'a mut #enum_name) -> Option<&'a mut #target_type> {
+                    match source {
+                        #variant_pattern => Some(#(#fields)*),
+                        _ => None,
+                    }
+                }
+            }
+        )
+    } else {
+        quote!()
+    };
+
+    Ok(quote!(
+        // Include the prism struct declaration
+        #[allow(dead_code)]
+        #[doc(hidden)]
+        #lens_visibility struct #prism_name;
+
+        // Include the `AffineLens` impl
+        #[allow(dead_code)]
+        impl pl_lens::AffineLens for #prism_name {
+            type Source = #enum_name;
+            type Target = #target_type;
+
+            #[inline(always)]
+            fn try_mutate(&self, source: &mut #enum_name, target: #target_type) {
+                if let #variant_pattern = source {
+                    let #target_pattern = target;
+                    #(*#fields = #values;)*
+                }
+            }
+        }
+
+        // Include the `AffineRefLens` impl (only if the target is a single field)
+        #ref_lens
+
+        // Include the `AffineValueLens` impl (only usable if the target is `Clone`)
+        #[allow(dead_code)]
+        impl pl_lens::AffineValueLens for #prism_name
+        where
+            for<'__lens> #target_type: Clone,
+        {
+            #[inline(always)]
+            #[allow(unreachable_patterns)]
+            fn try_get(&self, source: &#enum_name) -> Option<#target_type> {
+                match source {
+                    #variant_pattern => Some(#target_clone),
+                    _ => None,
+                }
+            }
+        }
+
+        // Include the `Prism` impl
+        #[allow(dead_code)]
+        impl pl_lens::Prism for #prism_name {
+            #[inline(always)]
+            fn review(&self, target: #target_type) -> #enum_name {
+                let #target_pattern = target;
+                #variant_expr
+            }
+        }
+
+        // Include the `HasTargetLenses` impl (only if the target is a single field, and only
+        // usable if it has lenses)
+        #target_lenses
+    ))
+}
+
+/// Generates the optic impls for the `<EnumName><VariantName>Lenses` struct of the given struct
+/// variant, which (in addition to enumerating the lenses for the fields of the variant) is an
+/// `AffineLens` that focuses on the whole enum value when it is that variant, and whose target
+/// lenses are the lenses for the fields.  This is what allows `lens!(Shape.Rect.width)` to be
+/// composed from `ShapeLenses.Rect` and `ShapeRectLenses.width`.
+fn struct_variant_optic(
+    input: &DeriveInput,
+    variant: &Variant,
+    lenses_struct_name: &syn::Ident,
+    lenses_value_fields: &[TokenStream2],
+) -> TokenStream2 {
+    let enum_name = &input.ident;
+    let variant_name = &variant.ident;
+    let variant_pattern = quote!(#enum_name::#variant_name { .. });
+
+    quote!(
+        // Include the `AffineLens` impl
+        #[allow(dead_code)]
+        impl pl_lens::AffineLens for #lenses_struct_name {
+            type Source = #enum_name;
+            type Target = #enum_name;
+
+            #[inline(always)]
+            fn try_mutate(&self, source: &mut #enum_name, target: #enum_name) {
+                if let #variant_pattern = source {
+                    *source = target
+                }
+            }
+        }
+
+        // Include the `AffineRefLens` impl
+        #[allow(dead_code)]
+        impl pl_lens::AffineRefLens for #lenses_struct_name {
+            #[inline(always)]
+            #[allow(unreachable_patterns)]
+            fn try_get_ref<'a>(&self, source: &'a #enum_name) -> Option<&'a #enum_name> {
+                match source {
+                    #variant_pattern => Some(source),
+                    _ => None,
+                }
+            }
+
+            #[inline(always)]
+            #[allow(unreachable_patterns)]
+            fn try_get_mut_ref<'a>(&self, source: &'a mut #enum_name) -> Option<&'a mut #enum_name> {
+                match source {
+                    #variant_pattern => Some(source),
+                    _ => None,
+                }
+            }
+        }
+
+        // Include the `AffineValueLens` impl (only usable if the enum is `Clone`)
+        #[allow(dead_code)]
+        impl pl_lens::AffineValueLens for #lenses_struct_name
+        where
+            for<'__lens> #enum_name: Clone,
+        {
+            #[inline(always)]
+            #[allow(unreachable_patterns)]
+            fn try_get(&self, source: &#enum_name) -> Option<#enum_name> {
+                match source {
+                    #variant_pattern => Some(source.clone()),
+                    _ => None,
+                }
+            }
+        }
+
+        // Include the `HasTargetLenses` impl, which resolves to the lenses for the fields
+        #[allow(dead_code)]
+        impl pl_lens::HasTargetLenses for #lenses_struct_name {
+            type TargetLenses = #lenses_struct_name;
+
+            #[inline(always)]
+            fn target_lenses(&self) -> #lenses_struct_name {
+                #lenses_struct_name {
+                    #(#lenses_value_fields),*
+                }
+            }
+        }
+    )
+}
+
+/// Generates an `AffineLens` for the given field of a struct variant.
+fn struct_variant_field_lens(
+    input: &DeriveInput,
+    variant: &Variant,
+    field: &Field,
+    lens_name: &syn::Ident,
+) -> syn::Result<TokenStream2> {
+    let enum_name = &input.ident;
+    let lens_visibility = &input.vis;
+    let variant_name = &variant.ident;
+    let field_name = field_ident(field)?;
+    let field_type = &field.ty;
+    let field_type = replace_self_type(quote!(#field_type), enum_name);
+    let field_options = parse_field_options(&field.attrs)?;
+    check_no_struct_field_options(&field_options)?;
+    let target_lenses = target_lenses_impl(lens_name, &input.generics, &field_options, &field_type);
+
+    let lens_impls = field_lens_impls(
+        &quote!(#lens_name),
+        &input.generics,
+        &quote!(#enum_name),
+        &field_type,
+        0,
+        &FieldAccess::VariantPattern(quote!(#enum_name::#variant_name { #field_name: field, .. })),
+    );
+
+    Ok(quote!(
+        // Include the lens struct declaration
+        #[allow(dead_code)]
+        #[doc(hidden)]
+        #lens_visibility struct #lens_name;
+
+        // Include the `AffineLens`, `AffineRefLens` and `AffineValueLens` impls
+        #lens_impls
+
+        // Include the `HasTargetLenses` impl (only usable if the target has lenses)
+        #target_lenses
+    ))
+}
+
+/// Returns the fields (along with their index in the first variant) that are shared by every
+/// variant in the given enum, i.e., fields with the same name and type that appear in every
+/// variant.  This is only possible if every variant is a struct variant.
+fn shared_fields(data_enum: &DataEnum) -> Vec<(u64, &Field)> {
+    let first_variant = match data_enum.variants.first() {
+        Some(variant) => variant,
+        None => return vec![],
+    };
+    first_variant
+        .fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| {
+            data_enum
+                .variants
+                .iter()
+                .all(|variant| find_matching_field(variant, field).is_some())
+        })
+        .map(|(index, field)| (index as u64, field))
+        .collect()
+}
+
+/// Returns the named field in the given variant that has the same name and type as the given
+/// field, if there is one.
+fn find_matching_field<'a>(variant: &'a Variant, field: &Field) -> Option<&'a Field> {
+    let field_type = &field.ty;
+    let field_type_str = quote!(#field_type).to_string();
+    match &variant.fields {
+        Fields::Named(fields) => fields.named.iter().find(|other| {
+            let other_type = &other.ty;
+            other.ident.is_some()
+                && other.ident == field.ident
+                && quote!(#other_type).to_string() == field_type_str
+        }),
+        _ => None,
+    }
+}
+
+/// Generates a `Lens` for the given field, which must be shared by every variant in the enum.
+fn shared_field_lens(
+    input: &DeriveInput,
+    data_enum: &DataEnum,
+    field: &Field,
+    field_index: u64,
+    lens_name: &syn::Ident,
+) -> syn::Result<TokenStream2> {
+    let enum_name = &input.ident;
+    let lens_visibility = &input.vis;
+    let field_name = field_ident(field)?;
+    let field_type = &field.ty;
+    let field_type = replace_self_type(quote!(#field_type), enum_name);
+    let field_options = parse_field_options(&field.attrs)?;
+    check_no_struct_field_options(&field_options)?;
+    let target_lenses = target_lenses_impl(lens_name, &input.generics, &field_options, &field_type);
+
+    // Build a pattern that matches the field in any variant, for example:
+    //     Event::Created { timestamp: field, .. } | Event::Deleted { timestamp: field, .. }
+    let variant_patterns = data_enum.variants.iter().map(|variant| {
+        let variant_name = &variant.ident;
+        quote!(#enum_name::#variant_name { #field_name: field, .. })
+    });
+    let field_pattern = quote!(#(#variant_patterns)|*);
+
+    let lens_impls = field_lens_impls(
+        &quote!(#lens_name),
+        &input.generics,
+        &quote!(#enum_name),
+        &field_type,
+        field_index,
+        &FieldAccess::SharedPattern(field_pattern),
+    );
+
+    Ok(quote!(
+        // Include the lens struct declaration
+        #[allow(dead_code)]
+        #[doc(hidden)]
+        #lens_visibility struct #lens_name;
+
+        // Include the `Lens`, `RefLens` and `ValueLens` impls
+        #lens_impls
+
+        // Include the `HasTargetLenses` impl (only usable if the target has lenses)
+        #target_lenses
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::{parse_quote, Data};
+
+    #[test]
+    fn shared_fields_should_require_the_same_name_and_type_in_every_variant() {
+        let input: DeriveInput = parse_quote!(
+            enum Event {
+                Created {
+                    id: u32,
+                    timestamp: u64,
+                    name: String,
+                },
+                Renamed {
+                    timestamp: u64,
+                    id: u64,
+                    name: String,
+                },
+                Deleted {
+                    name: String,
+                    timestamp: u64,
+                },
+            }
+        );
+        let data_enum = match &input.data {
+            Data::Enum(data_enum) => data_enum,
+            _ => unreachable!(),
+        };
+        let shared: Vec<(u64, String)> = shared_fields(data_enum)
+            .into_iter()
+            .map(|(index, field)| (index, field.ident.as_ref().unwrap().to_string()))
+            .collect();
+        assert_eq!(
+            shared,
+            vec![(1, "timestamp".to_string()), (2, "name".to_string())]
+        );
+    }
+
+    #[test]
+    fn shared_fields_should_be_empty_if_any_variant_is_not_a_struct_variant() {
+        let input: DeriveInput = parse_quote!(
+            enum Event {
+                Created { id: u32 },
+                Deleted(u32),
+            }
+        );
+        let data_enum = match &input.data {
+            Data::Enum(data_enum) => data_enum,
+            _ => unreachable!(),
+        };
+        assert!(shared_fields(data_enum).is_empty());
+    }
+}
@@ -5,228 +5,1025 @@
 
 extern crate proc_macro;
 
+mod attrs;
+mod enums;
+mod module;
+mod newtype;
+mod tuple_struct;
+
+use std::collections::HashMap;
+
 use proc_macro::TokenStream;
-use quote::{format_ident, quote};
-use syn::{parse_macro_input, Data, DataStruct, DeriveInput, Fields, Visibility};
+use proc_macro2::{Group, Ident, Literal, TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::ext::IdentExt;
+use syn::{parse_macro_input, Data, DeriveInput, Field, Fields, GenericParam, Generics};
+
+use crate::attrs::{Accessors, ContainerOptions, FieldOptions};
 
 /// Handles the `#derive(Lenses)` applied to a struct by generating a `Lens` implementation for
-/// each field in the struct.
-#[proc_macro_derive(Lenses)]
+/// each field in the struct (for a tuple struct, each lens is named after the position of its
+/// field).  When applied to a newtype (a tuple struct with a single field), an `Iso` between the
+/// struct and the wrapped type is generated instead.  When applied to an enum, a `Prism` is
+/// generated for each unit or tuple variant (whose target is `()` for a unit variant), and an
+/// `AffineLens` is generated for each field of each struct variant.  With `#[lens(module)]`, the
+/// generated items are declared in a module named after the type.
+#[proc_macro_derive(Lenses, attributes(lens))]
 pub fn lenses_derive(input: TokenStream) -> TokenStream {
     // Parse the input tokens into a syntax tree
     let input = parse_macro_input!(input as DeriveInput);
 
-    // Check that the input type is a struct
-    let data_struct: DataStruct;
-    if let Data::Struct(s) = input.data {
-        data_struct = s
-    } else {
-        panic!("`#[derive(Lenses)]` may only be applied to structs")
+    // Build the output, or report the reason the input was rejected
+    let expanded = derive_lenses(&input).unwrap_or_else(|error| error.to_compile_error());
+
+    // Hand the output tokens back to the compiler
+    TokenStream::from(expanded)
+}
+
+/// Generates the lenses for the given struct, or returns an error (spanned to the offending
+/// item) if lenses cannot be derived for it.
+fn derive_lenses(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let options = attrs::parse_container_options(&input.attrs)?;
+    match &options.module {
+        // With `#[lens(module)]`, the generated items are declared in a child module (with
+        // their visibility adjusted to match) rather than alongside the type
+        Some(module_name) => {
+            let items = derive_type_lenses(&module::input_for_module(input), &options)?;
+            Ok(module::wrap_in_module(input, module_name, items))
+        }
+        None => derive_type_lenses(input, &options),
     }
+}
 
-    // Check that the struct has named fields, since that's the only
-    // type we support at the moment
-    let fields: Fields;
-    if let Fields::Named(_) = data_struct.fields {
-        fields = data_struct.fields
-    } else {
-        panic!("`#[derive(Lenses)]` may only be applied to structs with named fields")
+/// Generates the lenses for the given type, declared alongside the type.
+fn derive_type_lenses(
+    input: &DeriveInput,
+    options: &ContainerOptions,
+) -> syn::Result<TokenStream2> {
+    // Check that `#[lens(remote = "...")]` and `#[lens(fields = "...")]` are only used with
+    // structs with named fields, since those are the only types they make sense for at the moment
+    let has_named_fields = match &input.data {
+        Data::Struct(data_struct) => matches!(data_struct.fields, Fields::Named(_)),
+        _ => false,
+    };
+    if !has_named_fields {
+        if let Some(remote) = &options.remote {
+            return Err(syn::Error::new_spanned(remote, REMOTE_NAMED_FIELDS_ONLY));
+        }
+        if let Some(markers) = &options.fields {
+            return Err(syn::Error::new_spanned(markers, FIELDS_NAMED_FIELDS_ONLY));
+        }
+        if let Some(span) = options.methods {
+            return Err(syn::Error::new(span, METHODS_NAMED_FIELDS_ONLY));
+        }
+        if let Some(span) = options.consts {
+            return Err(syn::Error::new(span, CONSTS_NAMED_FIELDS_ONLY));
+        }
     }
 
-    // Extract the struct name
+    // Check that `#[lens(methods)]` is not used with `#[lens(remote = "...")]`, since the
+    // methods would have to be declared on the foreign struct itself
+    if let (Some(span), Some(_)) = (options.methods, &options.remote) {
+        return Err(syn::Error::new(span, METHODS_NOT_REMOTE));
+    }
+
+    // Check that the input type is a struct with named fields, since that's the only
+    // type we support at the moment (other than newtypes and enums, which are handled
+    // separately)
+    let fields = match &input.data {
+        Data::Struct(data_struct) => match &data_struct.fields {
+            Fields::Named(fields) => &fields.named,
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                return newtype::derive_newtype_lenses(input, &fields.unnamed[0]);
+            }
+            Fields::Unnamed(fields) => {
+                return tuple_struct::derive_tuple_struct_lenses(input, &fields.unnamed);
+            }
+            Fields::Unit => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    UNIT_STRUCTS_UNSUPPORTED,
+                ));
+            }
+        },
+        Data::Enum(data_enum) => {
+            check_not_generic(input)?;
+            return enums::derive_enum_lenses(input, data_enum);
+        }
+        Data::Union(data_union) => {
+            return Err(syn::Error::new_spanned(
+                data_union.union_token,
+                STRUCTS_ONLY,
+            ));
+        }
+    };
+
+    // Extract the struct name and generics (the lens types for a generic struct have the same
+    // generic parameters as the struct, for example `PageItemsLens<T>` for `Page<T>`)
     let struct_name = &input.ident;
+    let generics = &input.generics;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_predicates = where_predicates(generics);
 
-    // Determine the visibility of the lens struct
-    let lens_visibility = match input.vis {
-        Visibility::Public(..) => quote!(pub),
-        // TODO: Handle `Crate` and `Restricted` visibliity
-        Visibility::Crate(..) => quote!(),
-        Visibility::Restricted(..) => quote!(),
-        Visibility::Inherited => quote!(),
+    // With `#[lens(remote = "...")]`, the struct restates the fields of a foreign struct, and the
+    // lenses focus on the foreign struct instead (the generated items are still named after the
+    // local struct, since two foreign types in different modules may share a name)
+    let (source_type, type_name) = match &options.remote {
+        Some(remote) => {
+            let type_name = remote.segments.last().unwrap().ident.clone();
+            (remote.to_token_stream(), type_name)
+        }
+        None => (quote!(#struct_name #ty_generics), struct_name.clone()),
     };
 
+    // The lens structs are declared alongside the struct, so they share its visibility
+    let lens_visibility = &input.vis;
+
     // Generate lenses for each field in the struct
-    let lens_items = fields.iter().enumerate().map(|(index, field)| {
-        if let Some(field_name) = &field.ident {
+    let lens_items = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let field_name = field_ident(field)?;
             let field_index = index as u64;
+
+            // Any field type is allowed here; the field is treated as a leaf unless its type
+            // implements `HasLenses`, in which case `lens!` can reach into it.  Note that the
+            // field type is used within the lens impls below, where `Self` would refer to the
+            // lens type, so we replace any `Self` with the struct name.
             let field_type = &field.ty;
+            let field_type = replace_self_type(quote!(#field_type), &source_type);
 
             // Build the Lens name from the struct name and field name (for example, "StructFieldLens")
             let lens_name = format_ident!(
                 "{}{}Lens",
-                struct_name.to_string(),
-                to_camel_case(&field_name.to_string())
+                struct_name,
+                to_camel_case(&field_name.unraw().to_string())
             );
+            let lens_type = quote!(#lens_name #ty_generics);
 
-            // Build a `ValueLens` impl if the target is a primitive
-            // TODO: Should do this automatically for any target type that implements `Clone`
-            let value_lens = if is_primitive(&field.ty) {
-                quote!(
-                    #[allow(dead_code)]
-                    impl pl_lens::ValueLens for #lens_name {
-                        #[inline(always)]
-                        fn get(&self, source: &#struct_name) -> #field_type {
-                            (*source).#field_name.clone()
-                        }
-                    }
-                )
-            } else {
-                quote!()
-            };
-
-            quote!(
-                // Include the lens struct declaration
-                #[allow(dead_code)]
-                #[doc(hidden)]
-                #lens_visibility struct #lens_name;
+            let field_options = attrs::parse_field_options(&field.attrs)?;
 
-                // Include the `Lens` impl
-                #[allow(dead_code)]
-                impl pl_lens::Lens for #lens_name {
-                    type Source = #struct_name;
-                    type Target = #field_type;
+            // Build the `Lens`, `RefLens` and `ValueLens` impls, which either access the field
+            // directly, or with `#[lens(get = "...", set = "...")]`, go through the given getter
+            // and setter methods
+            let access = match &field_options.accessors {
+                Some(accessors) => FieldAccess::Accessors(accessors),
+                None => FieldAccess::Member(quote!(#field_name)),
+            };
+            let lens_impls = field_lens_impls(
+                &lens_type,
+                generics,
+                &source_type,
+                &field_type,
+                field_index,
+                &access,
+            );
 
-                    #[inline(always)]
-                    fn path(&self) -> pl_lens::LensPath {
-                        pl_lens::LensPath::new(#field_index)
-                    }
+            let target_lenses =
+                target_lenses_impl(&lens_name, generics, &field_options, &field_type);
+            let lens_struct = lens_struct(lens_visibility, &lens_name, generics, &source_type);
 
-                    #[inline(always)]
-                    fn mutate<'a>(&self, source: &'a mut #struct_name, target: #field_type) {
-                        source.#field_name = target
-                    }
-                }
-
-                // Include the `RefLens` impl
-                #[allow(dead_code)]
-                impl pl_lens::RefLens for #lens_name {
-                    #[inline(always)]
-                    fn get_ref<'a>(&self, source: &'a #struct_name) -> &'a #field_type {
-                        &(*source).#field_name
-                    }
+            Ok(quote!(
+                // Include the lens struct declaration
+                #lens_struct
 
-                    #[inline(always)]
-                    fn get_mut_ref<'a>(&self, source: &'a mut #struct_name) -> &'a mut #field_type {
-                        &mut (*source).#field_name
-                    }
-                }
+                // Include the `Lens`, `RefLens` and `ValueLens` impls
+                #lens_impls
 
-                // Include the `ValueLens` impl (only if it should be defined)
-                #value_lens
-            )
-        } else {
-            // This should be unreachable, since we already verified above that the struct
-            // only contains named fields
-            panic!("`#[derive(Lenses)]` may only be applied to structs with named fields")
-        }
-    });
+                // Include the `HasTargetLenses` impl (only usable if the target has lenses)
+                #target_lenses
+            ))
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
 
     // Build a `<StructName>Lenses` struct that enumerates the available lenses
     // for each field in the struct, for example:
-    //     struct Struct2Lenses {
+    //     struct Struct2Lenses<P = IdentityLens<Struct2>> {
     //         int32: Struct2Int32Lens,
-    //         struct1: Struct2Struct1Lens,
-    //         struct1_lenses: Struct1Lenses
+    //         struct1: Struct2Struct1Lens
     //     }
+    // Note that the lenses for nested structs are not included here; those are
+    // resolved from the field type (via `HasLenses`) by the `lens!` macro.  The type
+    // parameter is the prefix lens used by the lens builder methods (see below).
+    // (For a generic struct, the prefix lens parameter follows the parameters of the struct,
+    // and is renamed if the struct already has a parameter named `P`.)
     let lenses_struct_name = format_ident!("{}Lenses", struct_name);
-    let lenses_struct_fields = fields.iter().map(|field| {
-        if let Some(field_name) = &field.ident {
+    let lenses_type = quote!(#lenses_struct_name #ty_generics);
+    let prefix = if generics.type_params().any(|param| param.ident == "P") {
+        format_ident!("__P")
+    } else {
+        format_ident!("P")
+    };
+    let field_lenses = fields
+        .iter()
+        .map(|field| {
+            let field_name = field_ident(field)?;
             let field_lens_name = format_ident!(
                 "{}{}Lens",
                 struct_name,
-                to_camel_case(&field_name.to_string())
+                to_camel_case(&field_name.unraw().to_string())
             );
-            if is_primitive(&field.ty) {
-                quote!(#field_name: #field_lens_name)
-            } else {
-                let field_parent_lenses_field_name = format_ident!("{}_lenses", field_name);
-                let field_parent_lenses_type_name =
-                    format_ident!("{}Lenses", to_camel_case(&field_name.to_string()));
-                quote!(
-                    #field_name: #field_lens_name,
-                    #field_parent_lenses_field_name: #field_parent_lenses_type_name
-                )
-            }
-        } else {
-            // This should be unreachable, since we already verified above that the struct
-            // only contains named fields
-            panic!("`#[derive(Lenses)]` may only be applied to structs with named fields")
-        }
-    });
+            let field_lens_type = quote!(#field_lens_name #ty_generics);
+            let field_lens_value = lens_value(&field_lens_name, generics);
+            Ok((field_name, field_lens_type, field_lens_value))
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+    let lenses_struct_fields = field_lenses.iter().map(
+        |(field_name, field_lens_type, _)| quote!(#lens_visibility #field_name: #field_lens_type),
+    );
+    let lenses_struct_doc = format!(
+        "The lenses for each field of `{}`.  Each field of this struct holds the lens for the \
+         field of the same name, and each method returns that lens composed with the prefix \
+         lens `{}`, for example `{}::lenses().field()`.",
+        type_name, prefix, struct_name
+    );
+    let mut lenses_struct_generics = generics.clone();
+    lenses_struct_generics
+        .params
+        .push(syn::parse_quote!(#prefix = pl_lens::IdentityLens<#source_type>));
     let lenses_struct = quote!(
         #[allow(dead_code)]
-        #[doc(hidden)]
-        #lens_visibility struct #lenses_struct_name {
-            #(#lenses_struct_fields),*
+        #[allow(non_snake_case)]
+        #[doc = #lenses_struct_doc]
+        #lens_visibility struct #lenses_struct_name #lenses_struct_generics
+        where
+            #where_predicates
+        {
+            #(#lenses_struct_fields,)*
+            __prefix: ::std::marker::PhantomData<fn() -> #prefix>
         }
     );
 
-    // Declare a `_<StructName>Lenses` instance that holds the available lenses
-    // for each field in the struct, for example:
-    //     const _Struct2Lenses: Struct2Lenses = Struct2Lenses {
+    // Build the `<StructName>Lenses` instance that holds the available lenses for each
+    // field in the struct, for example:
+    //     Struct2Lenses {
     //         int32: Struct2Int32Lens,
-    //         struct1: Struct2Struct1Lens,
-    //         struct1_lenses: _Struct1Lenses
-    //     };
-    let lenses_const_name = format_ident!("_{}Lenses", struct_name);
-    let lenses_const_fields = fields.iter().map(|field|
-        // TODO: Most of this is nearly identical to how the "Lenses" struct is declared,
-        // except for the underscore prefix in a couple places; might be good to consolidate
-        if let Some(field_name) = &field.ident {
-            let field_lens_name = format_ident!("{}{}Lens", struct_name, to_camel_case(&field_name.to_string()));
-            if is_primitive(&field.ty) {
-                quote!(#field_name: #field_lens_name)
-            } else {
-                let field_parent_lenses_field_name = format_ident!("{}_lenses", field_name);
-                let field_parent_lenses_type_name = format_ident!("_{}Lenses", to_camel_case(&field_name.to_string()));
-                quote!(
-                    #field_name: #field_lens_name,
-                    #field_parent_lenses_field_name: #field_parent_lenses_type_name
-                )
-            }
-        } else {
-            // This should be unreachable, since we already verified above that the struct
-            // only contains named fields
-            panic!("`#[derive(Lenses)]` may only be applied to structs with named fields")
+    //         struct1: Struct2Struct1Lens
+    //     }
+    let lenses_value_fields = field_lenses
+        .iter()
+        .map(|(field_name, _, field_lens_value)| quote!(#field_name: #field_lens_value));
+    let lenses_value = quote!(
+        #lenses_struct_name {
+            #(#lenses_value_fields,)*
+            __prefix: ::std::marker::PhantomData
         }
     );
-    let lenses_const = quote!(
+
+    // Implement `HasLenses` for the struct so that the lenses can be resolved from the
+    // struct type itself (by the `lens!` macro, or by generic code)
+    let has_lenses_impl = quote!(
         #[allow(dead_code)]
-        #[allow(non_upper_case_globals)]
-        #[doc(hidden)]
-        #lens_visibility const #lenses_const_name: #lenses_struct_name = #lenses_struct_name {
-            #(#lenses_const_fields),*
+        impl #impl_generics pl_lens::HasLenses for #struct_name #ty_generics
+        where
+            #where_predicates
+        {
+            type Lenses = #lenses_type;
+
+            #[inline(always)]
+            fn lenses() -> #lenses_type {
+                #lenses_value
+            }
+        }
+    );
+
+    // With `#[lens(fields = "path::to::markers")]`, implement `HasField` for each field marked
+    // with `#[lens(field)]`, using the marker type of the same name in the given module, for
+    // example:
+    //     impl HasField<markers::int32> for Struct2 {
+    //         type Type = i32;
+    //         type Lens = Struct2Int32Lens;
+    //         ...
+    //     }
+    // This allows generic code to work with the field of any type that has it (fields that aren't
+    // marked need no marker, so a module of markers can be shared by unrelated types)
+    let mut has_field_impls = vec![];
+    for field in fields {
+        let field_options = attrs::parse_field_options(&field.attrs)?;
+        let markers = match (&options.fields, field_options.field) {
+            (Some(markers), Some(_)) => markers,
+            (None, Some(span)) => return Err(syn::Error::new(span, FIELD_REQUIRES_FIELDS)),
+            (_, None) => continue,
         };
+        let field_name = field_ident(field)?;
+        let field_type = &field.ty;
+        let field_type = replace_self_type(quote!(#field_type), &source_type);
+        let field_lens_name = format_ident!(
+            "{}{}Lens",
+            struct_name,
+            to_camel_case(&field_name.unraw().to_string())
+        );
+        let field_lens_value = lens_value(&field_lens_name, generics);
+        has_field_impls.push(quote!(
+            #[allow(dead_code)]
+            impl #impl_generics pl_lens::HasField<#markers::#field_name> for #source_type
+            where
+                #where_predicates
+            {
+                type Type = #field_type;
+                type Lens = #field_lens_name #ty_generics;
+
+                #[inline(always)]
+                fn lens() -> #field_lens_name #ty_generics {
+                    #field_lens_value
+                }
+            }
+        ));
+    }
+
+    // With `#[lens(methods)]`, declare a `with_<field>` and `map_<field>` method on the struct
+    // for each field, for example:
+    //     impl Person {
+    //         pub fn with_name(self, value: String) -> Person { ... }
+    //         pub fn map_name<F: FnOnce(String) -> String>(self, f: F) -> Person { ... }
+    //     }
+    // Like the associated constants, each method shares the visibility of its field (or of the
+    // lens, if the lens goes through accessor methods)
+    let update_methods_impl = if options.methods.is_some() {
+        let update_methods = fields
+            .iter()
+            .map(|field| {
+                let field_name = field_ident(field)?;
+                let field_options = attrs::parse_field_options(&field.attrs)?;
+                let field_visibility = match field_options.accessors {
+                    Some(_) => lens_visibility,
+                    None => &field.vis,
+                };
+                let field_type = &field.ty;
+                let field_type = replace_self_type(quote!(#field_type), &source_type);
+                let field_lens_name = format_ident!(
+                    "{}{}Lens",
+                    struct_name,
+                    to_camel_case(&field_name.unraw().to_string())
+                );
+                let field_lens_value = lens_value(&field_lens_name, generics);
+                let with_name = format_ident!("with_{}", field_name, span = field_name.span());
+                let map_name = format_ident!("map_{}", field_name, span = field_name.span());
+                let with_doc = format!(
+                    "Returns the struct with the `{}` field set to the given value.",
+                    field_name
+                );
+                let map_doc = format!(
+                    "Returns the struct with the `{}` field replaced by the result of applying \
+                     the given function to its current value.",
+                    field_name
+                );
+
+                // The mapped value is moved out of the field through the lens and back in, so
+                // that the function can consume it (which also works for a struct that
+                // implements `Drop`); with accessor methods, it's retrieved from the getter
+                // instead
+                let map_body = match field_options.accessors {
+                    Some(_) => quote!(
+                        let value = pl_lens::ValueLens::get(&#field_lens_value, &self);
+                        pl_lens::Lens::set(&#field_lens_value, self, f(value))
+                    ),
+                    None => quote!(
+                        pl_lens::map_with_fn(&#field_lens_value, self, f)
+                    ),
+                };
+
+                Ok(quote!(
+                    #[doc = #with_doc]
+                    #[inline(always)]
+                    #field_visibility fn #with_name(self, value: #field_type) -> #source_type {
+                        pl_lens::Lens::set(&#field_lens_value, self, value)
+                    }
+
+                    #[doc = #map_doc]
+                    #[inline(always)]
+                    #field_visibility fn #map_name<F>(self, f: F) -> #source_type
+                    where
+                        F: FnOnce(#field_type) -> #field_type,
+                    {
+                        #map_body
+                    }
+                ))
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        quote!(
+            #[allow(dead_code)]
+            #[allow(non_snake_case)]
+            impl #impl_generics #struct_name #ty_generics
+            where
+                #where_predicates
+            {
+                #(#update_methods)*
+            }
+        )
+    } else {
+        quote!()
+    };
+
+    // Build a method for each field that returns the prefix lens composed with the lens for
+    // that field (since the target of the prefix lens must be `'static` to be composed, this
+    // requires each parameter of a generic struct to be `'static`), for example:
+    //     fn struct1(&self) -> LensChain<ComposedLens<P, Struct2Struct1Lens>>
+    // The result is wrapped in a `LensChain`, which dereferences to the builder for the
+    // field type (if it has one), so that `Struct3::lenses().struct2().struct1()` works
+    let builder_methods = field_lenses.iter().map(
+        |(field_name, field_lens_type, field_lens_value)| {
+            let method_doc = format!(
+                "Returns the lens for the `{}` field of `{}`, composed with the prefix lens `{}`.",
+                field_name, type_name, prefix
+            );
+            quote!(
+                #[doc = #method_doc]
+                #[inline(always)]
+                #lens_visibility fn #field_name(
+                    &self,
+                ) -> pl_lens::LensChain<pl_lens::ComposedLens<#prefix, #field_lens_type>> {
+                    pl_lens::LensChain::new(pl_lens::compose(#prefix::default(), #field_lens_value))
+                }
+            )
+        },
     );
+    let mut builder_generics = generics.clone();
+    builder_generics.params.push(syn::parse_quote!(#prefix));
+    let (builder_impl_generics, builder_ty_generics, _) = builder_generics.split_for_impl();
+    let static_predicates = static_predicates(generics);
+    let builder_impl = quote!(
+        #[allow(dead_code)]
+        #[allow(non_snake_case)]
+        impl #builder_impl_generics #lenses_struct_name #builder_ty_generics
+        where
+            #where_predicates
+            #static_predicates
+            #prefix: pl_lens::RefLens<Target = #source_type> + Default,
+        {
+            #(#builder_methods)*
+        }
+    );
+
+    // Implement `HasFieldLens` for the `<StructName>Lenses` struct for each field, so that
+    // `lens!` can look up the lens for a field by name
+    let lenses_struct_type = quote!(#lenses_struct_name #builder_ty_generics);
+    let lens_entry_impls = field_lenses.iter().map(|(field_name, field_lens_type, _)| {
+        lens_entry_impl(
+            &lenses_struct_type,
+            &builder_generics,
+            field_name,
+            field_lens_type,
+            quote!(pl_lens::LensKind),
+        )
+    });
+
+    // Implement `HasLensBuilder` for the struct so that a `LensChain` whose target is the
+    // struct can dereference to the builder.  Since the builder holds no state, a reference
+    // to it can be promoted to a constant.  (This isn't possible for a foreign struct, since
+    // the impl would have to be for the foreign struct itself.)
+    let has_lens_builder_impl = if options.remote.is_some() {
+        quote!()
+    } else {
+        quote!(
+        #[allow(dead_code)]
+        impl #builder_impl_generics pl_lens::HasLensBuilder<#prefix> for #struct_name #ty_generics
+        where
+            #where_predicates
+            #static_predicates
+            #prefix: 'static,
+        {
+            type Builder = #lenses_struct_name #builder_ty_generics;
+
+            #[inline(always)]
+            fn builder() -> &'static #lenses_struct_name #builder_ty_generics {
+                &#lenses_value
+            }
+        }
+        )
+    };
+
+    // With `#[lens(consts)]`, declare an associated constant on the struct for each lens, for
+    // example:
+    //     impl Person {
+    //         pub const NAME: PersonNameLens = PersonNameLens;
+    //     }
+    // Each constant shares the visibility of its field, so that `Person::NAME` is only
+    // accessible where `person.name` is (unless the lens goes through accessor methods, in
+    // which case the field is typically private, so the constant shares the visibility of
+    // the lens instead).  Since the constants are named in screaming snake case, two fields
+    // may map to the same name (e.g. `url` and `URL`), which is reported against the latter.
+    let lens_consts_impl = if options.consts.is_some() {
+        let mut const_names: HashMap<String, &Ident> = HashMap::new();
+        let lens_consts = fields
+            .iter()
+            .map(|field| {
+                let field_name = field_ident(field)?;
+                let field_options = attrs::parse_field_options(&field.attrs)?;
+                let field_visibility = match field_options.accessors {
+                    Some(_) => lens_visibility,
+                    None => &field.vis,
+                };
+                let const_name = format_ident!(
+                    "{}",
+                    to_screaming_snake_case(&field_name.unraw().to_string()),
+                    span = field_name.span()
+                );
+                if let Some(other) = const_names.insert(const_name.to_string(), field_name) {
+                    return Err(syn::Error::new_spanned(
+                        field_name,
+                        format!(
+                            "the lens constants for the fields `{}` and `{}` would both be \
+                             named `{}`",
+                            other, field_name, const_name
+                        ),
+                    ));
+                }
+                let field_lens_name = format_ident!(
+                    "{}{}Lens",
+                    struct_name,
+                    to_camel_case(&field_name.unraw().to_string())
+                );
+                let field_lens_value = lens_value(&field_lens_name, generics);
+                let const_doc = format!("The lens for the `{}` field.", field_name);
+                Ok(quote!(
+                    #[doc = #const_doc]
+                    #field_visibility const #const_name: #field_lens_name #ty_generics = #field_lens_value;
+                ))
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        quote!(
+            #[allow(dead_code)]
+            impl #impl_generics #struct_name #ty_generics
+            where
+                #where_predicates
+            {
+                #(#lens_consts)*
+            }
+        )
+    } else {
+        quote!()
+    };
 
     // Build the output
-    let expanded = quote! {
+    Ok(quote! {
         #(#lens_items)*
 
+        #lens_consts_impl
+
+        #(#has_field_impls)*
+
+        #update_methods_impl
+
         #lenses_struct
 
-        #lenses_const
+        #has_lenses_impl
+
+        #(#lens_entry_impls)*
+
+        #builder_impl
+
+        #has_lens_builder_impl
+    })
+}
+
+const STRUCTS_ONLY: &str = "`#[derive(Lenses)]` may only be applied to structs and enums";
+const REMOTE_NAMED_FIELDS_ONLY: &str =
+    "`#[lens(remote = \"...\")]` may only be applied to structs with named fields";
+const FIELDS_NAMED_FIELDS_ONLY: &str =
+    "`#[lens(fields = \"...\")]` may only be applied to structs with named fields";
+const FIELD_NAMED_FIELDS_ONLY: &str =
+    "`#[lens(field)]` may only be applied to the fields of structs with named fields";
+const FIELD_REQUIRES_FIELDS: &str =
+    "`#[lens(field)]` requires the module of field markers to be given with `#[lens(fields = \"...\")]` on the struct";
+const ACCESSORS_STRUCTS_ONLY: &str =
+    "`#[lens(get = \"...\", set = \"...\")]` may only be applied to the fields of structs with named fields";
+const METHODS_NAMED_FIELDS_ONLY: &str =
+    "`#[lens(methods)]` may only be applied to structs with named fields";
+const CONSTS_NAMED_FIELDS_ONLY: &str =
+    "`#[lens(consts)]` may only be applied to structs with named fields";
+const METHODS_NOT_REMOTE: &str =
+    "`#[lens(methods)]` cannot be combined with `#[lens(remote = \"...\")]`";
+const UNIT_STRUCTS_UNSUPPORTED: &str =
+    "`#[derive(Lenses)]` may not be applied to unit structs, since they have no fields";
+const NAMED_FIELDS_ONLY: &str =
+    "`#[derive(Lenses)]` may only be applied to structs with named fields";
+const GENERIC_ENUMS_UNSUPPORTED: &str =
+    "`#[derive(Lenses)]` does not support generic enums (only structs may be generic)";
+
+/// Return an error (spanned to the generic parameters) if the given type is generic.  (Only
+/// structs may be generic; the prisms and lenses generated for an enum are not.)
+fn check_not_generic(input: &DeriveInput) -> syn::Result<()> {
+    if input.generics.params.is_empty() {
+        Ok(())
+    } else {
+        Err(syn::Error::new_spanned(
+            &input.generics,
+            GENERIC_ENUMS_UNSUPPORTED,
+        ))
+    }
+}
+
+/// Return the predicates of the where clause of the given generics (each followed by a comma, so
+/// that more predicates can be appended).
+fn where_predicates(generics: &Generics) -> TokenStream2 {
+    match &generics.where_clause {
+        Some(where_clause) => {
+            let predicates = where_clause.predicates.iter();
+            quote!(#(#predicates,)*)
+        }
+        None => quote!(),
+    }
+}
+
+/// Return a `'static` bound (each followed by a comma) for each type and lifetime parameter of
+/// the given generics.
+fn static_predicates(generics: &Generics) -> TokenStream2 {
+    let predicates = generics.params.iter().filter_map(|param| match param {
+        GenericParam::Type(param) => {
+            let ident = &param.ident;
+            Some(quote!(#ident: 'static))
+        }
+        GenericParam::Lifetime(param) => {
+            let lifetime = &param.lifetime;
+            Some(quote!(#lifetime: 'static))
+        }
+        GenericParam::Const(_) => None,
+    });
+    quote!(#(#predicates,)*)
+}
+
+/// How the target of a generated field lens is reached from the source.
+pub(crate) enum FieldAccess<'a> {
+    /// Through the given member of the source, e.g. `name` or `0`.
+    Member(TokenStream2),
+
+    /// Through the given getter and setter methods (from `#[lens(get = "...", set = "...")]`), in
+    /// which case there is no `RefLens` impl, since the getter returns the field by value.
+    Accessors(&'a Accessors),
+
+    /// Through the `field` binding of the given pattern, which matches every variant of an enum
+    /// (for a field that is shared by every variant).
+    SharedPattern(TokenStream2),
+
+    /// Through the `field` binding of the given pattern, which only matches a single variant of
+    /// an enum (for a field of a struct variant), in which case the lens is an affine lens.
+    VariantPattern(TokenStream2),
+}
+
+/// Generates the `Lens`, `RefLens` and `ValueLens` impls for the given field lens (or for the
+/// field of a struct variant, the `AffineLens`, `AffineRefLens` and `AffineValueLens` impls),
+/// whose target is reached from the source as given by `access`.  (The field index is the path of
+/// the lens, which an affine lens doesn't have.)
+pub(crate) fn field_lens_impls(
+    lens_type: &TokenStream2,
+    generics: &Generics,
+    source_type: &TokenStream2,
+    field_type: &TokenStream2,
+    field_index: u64,
+    access: &FieldAccess,
+) -> TokenStream2 {
+    let (impl_generics, _, _) = generics.split_for_impl();
+    let where_predicates = where_predicates(generics);
+
+    // The `[Affine]ValueLens` impl is available whenever the target type implements `Clone`
+    // (unless the lens goes through accessor methods); the bound is higher-ranked so that the
+    // compiler defers checking it rather than rejecting the impl outright for target types that
+    // are not `Clone`
+    let clone_predicate = quote!(for<'__lens> #field_type: Clone,);
+
+    // The field of a struct variant may be absent, so its lens is an affine lens
+    if let FieldAccess::VariantPattern(pattern) = access {
+        return quote!(
+            // Include the `AffineLens` impl
+            #[allow(dead_code)]
+            impl #impl_generics pl_lens::AffineLens for #lens_type
+            where
+                #where_predicates
+            {
+                type Source = #source_type;
+                type Target = #field_type;
+
+                #[inline(always)]
+                fn try_mutate(&self, source: &mut #source_type, target: #field_type) {
+                    if let #pattern = source {
+                        *field = target
+                    }
+                }
+            }
+
+            // Include the `AffineRefLens` impl
+            #[allow(dead_code)]
+            impl #impl_generics pl_lens::AffineRefLens for #lens_type
+            where
+                #where_predicates
+            {
+                #[inline(always)]
+                #[allow(unreachable_patterns)]
+                fn try_get_ref<'__source>(&self, source: &'__source #source_type) -> Option<&'__source #field_type> {
+                    match source {
+                        #pattern => Some(field),
+                        _ => None,
+                    }
+                }
+
+                #[inline(always)]
+                #[allow(unreachable_patterns)]
+                fn try_get_mut_ref<'__source>(&self, source: &'__source mut #source_type) -> Option<&'__source mut #field_type> {
+                    match source {
+                        #pattern => Some(field),
+                        _ => None,
+                    }
+                }
+            }
+
+            // Include the `AffineValueLens` impl (only usable if the target is `Clone`)
+            #[allow(dead_code)]
+            impl #impl_generics pl_lens::AffineValueLens for #lens_type
+            where
+                #where_predicates
+                #clone_predicate
+            {
+                #[inline(always)]
+                #[allow(unreachable_patterns)]
+                fn try_get(&self, source: &#source_type) -> Option<#field_type> {
+                    match source {
+                        #pattern => Some(field.clone()),
+                        _ => None,
+                    }
+                }
+            }
+        );
+    }
+
+    // Build the body of each method: `mutate`, along with `get_ref`, `get_mut_ref` and `get`
+    // (for the lenses that have a `RefLens` impl), or just `get` (for the lenses that go through
+    // accessor methods)
+    let (mutate_body, ref_bodies, get_body, value_predicate) = match access {
+        FieldAccess::Member(member) => (
+            quote!(source.#member = target),
+            Some((quote!(&(*source).#member), quote!(&mut (*source).#member))),
+            quote!((*source).#member.clone()),
+            clone_predicate,
+        ),
+        FieldAccess::Accessors(accessors) => {
+            let getter = &accessors.get;
+            let setter = &accessors.set;
+            (
+                quote!(source.#setter(target);),
+                None,
+                quote!(source.#getter()),
+                quote!(),
+            )
+        }
+        FieldAccess::SharedPattern(pattern) => {
+            let field_ref = quote!(match source {
+                #pattern => field,
+            });
+            (
+                quote!(match source {
+                    #pattern => *field = target,
+                }),
+                Some((field_ref.clone(), field_ref)),
+                quote!(match source {
+                    #pattern => field.clone(),
+                }),
+                clone_predicate,
+            )
+        }
+        FieldAccess::VariantPattern(_) => unreachable!("handled above"),
     };
+    let ref_lens = match ref_bodies {
+        Some((get_ref_body, get_mut_ref_body)) => quote!(
+            #[allow(dead_code)]
+            impl #impl_generics pl_lens::RefLens for #lens_type
+            where
+                #where_predicates
+            {
+                #[inline(always)]
+                fn get_ref<'__source>(&self, source: &'__source #source_type) -> &'__source #field_type {
+                    #get_ref_body
+                }
 
-    // Hand the output tokens back to the compiler
-    TokenStream::from(expanded)
+                #[inline(always)]
+                fn get_mut_ref<'__source>(&self, source: &'__source mut #source_type) -> &'__source mut #field_type {
+                    #get_mut_ref_body
+                }
+            }
+        ),
+        None => quote!(),
+    };
+
+    quote!(
+        // Include the `Lens` impl
+        #[allow(dead_code)]
+        impl #impl_generics pl_lens::Lens for #lens_type
+        where
+            #where_predicates
+        {
+            type Source = #source_type;
+            type Target = #field_type;
+
+            #[inline(always)]
+            fn path(&self) -> pl_lens::LensPath {
+                pl_lens::LensPath::new(#field_index)
+            }
+
+            #[inline(always)]
+            fn mutate(&self, source: &mut #source_type, target: #field_type) {
+                #mutate_body
+            }
+        }
+
+        // Include the `RefLens` impl (unless the lens goes through accessor methods)
+        #ref_lens
+
+        // Include the `ValueLens` impl (only usable if the target is `Clone`, unless the lens
+        // goes through accessor methods)
+        #[allow(dead_code)]
+        impl #impl_generics pl_lens::ValueLens for #lens_type
+        where
+            #where_predicates
+            #value_predicate
+        {
+            #[inline(always)]
+            fn get(&self, source: &#source_type) -> #field_type {
+                #get_body
+            }
+        }
+    )
 }
 
-/// Return true if the given type should be considered a primitive, i.e., whether
-/// it doesn't have lenses defined for it.
-fn is_primitive(ty: &syn::Type) -> bool {
-    let type_str = quote!(#ty).to_string();
-    match type_str.as_ref() {
-        // XXX: This is quick and dirty; we need a more reliable way to
-        // know whether the field is a struct type for which there are
-        // lenses derived
-        "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" | "f32" | "f64" | "String" => {
-            true
+/// Generates the declaration of the given lens struct.  The lens holds no state, so it can be
+/// built from nothing when it is used as a prefix by the lens builders; for a generic struct, the
+/// lens holds a marker for the source type, so that it has the same generic parameters.
+fn lens_struct(
+    visibility: &syn::Visibility,
+    lens_name: &Ident,
+    generics: &Generics,
+    source_type: &TokenStream2,
+) -> TokenStream2 {
+    if generics.params.is_empty() {
+        return quote!(
+            #[allow(dead_code)]
+            #[doc(hidden)]
+            #[derive(Default)]
+            #visibility struct #lens_name;
+        );
+    }
+
+    // `Default` is implemented by hand, since deriving it would require each type parameter to
+    // implement `Default`
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_predicates = where_predicates(generics);
+    quote!(
+        #[allow(dead_code)]
+        #[doc(hidden)]
+        #visibility struct #lens_name #generics (
+            ::std::marker::PhantomData<fn(#source_type) -> #source_type>,
+        )
+        where
+            #where_predicates;
+
+        #[allow(dead_code)]
+        impl #impl_generics Default for #lens_name #ty_generics
+        where
+            #where_predicates
+        {
+            #[inline(always)]
+            fn default() -> Self {
+                #lens_name(::std::marker::PhantomData)
+            }
         }
-        _ => false,
+    )
+}
+
+/// Returns an expression that builds an instance of the given lens struct (declared by
+/// `lens_struct`), which is usable in a constant.
+fn lens_value(lens_name: &Ident, generics: &Generics) -> TokenStream2 {
+    if generics.params.is_empty() {
+        quote!(#lens_name)
+    } else {
+        quote!(#lens_name(::std::marker::PhantomData))
     }
 }
 
+/// Returns the `FieldName` type for the given field (or variant) name, which is how the
+/// `HasFieldLens` impls are keyed.  (The hash is computed by `pl_lens::field_name_hash`, just as
+/// it is for the names used by the `lens!` macro.)
+fn name_type(name: &Ident) -> TokenStream2 {
+    let name = Literal::string(&name.unraw().to_string());
+    quote!(pl_lens::FieldName<{ pl_lens::field_name_hash(#name) }>)
+}
+
+/// Generates a `HasFieldLens` impl for the entry of the given name in the given lenses struct,
+/// which allows `lens!` to look up the entry by name, along with the given kind of its optic
+/// (e.g. `LensKind` for a field, or `AffineKind` for a variant).  The impl is marked
+/// `do_not_recommend`, so that an unknown name is reported as such, rather than with a list of
+/// the (hashed) names of the entries that do exist.
+fn lens_entry_impl(
+    lenses_type: &TokenStream2,
+    generics: &Generics,
+    name: &Ident,
+    optic_type: &TokenStream2,
+    kind: TokenStream2,
+) -> TokenStream2 {
+    let mut generics = generics.clone();
+    generics.params.push(syn::parse_quote!(__Label));
+    generics.params.push(syn::parse_quote!(__Source));
+    let (impl_generics, _, _) = generics.split_for_impl();
+    let where_predicates = where_predicates(&generics);
+    let name_type = name_type(name);
+    quote!(
+        #[allow(dead_code)]
+        #[diagnostic::do_not_recommend]
+        impl #impl_generics pl_lens::HasFieldLens<#name_type, __Label, __Source, #optic_type, #kind>
+            for #lenses_type
+        where
+            #where_predicates
+        {
+            #[inline(always)]
+            fn field_lens(
+                _source: ::std::marker::PhantomData<__Source>,
+                lenses: Self,
+            ) -> (#optic_type, #kind) {
+                (lenses.#name, #kind)
+            }
+        }
+    )
+}
+
+/// Return an error (spanned to the offending option) if the given field options include accessor
+/// methods or a `HasField` marker, which are only supported for the fields of structs with named
+/// fields.
+fn check_no_struct_field_options(field_options: &FieldOptions) -> syn::Result<()> {
+    if let Some(accessors) = &field_options.accessors {
+        return Err(syn::Error::new_spanned(
+            &accessors.get,
+            ACCESSORS_STRUCTS_ONLY,
+        ));
+    }
+    if let Some(span) = field_options.field {
+        return Err(syn::Error::new(span, FIELD_NAMED_FIELDS_ONLY));
+    }
+    Ok(())
+}
+
+/// Return the name of the given field, or an error (spanned to the field) if it is unnamed.
+fn field_ident(field: &Field) -> syn::Result<&Ident> {
+    field
+        .ident
+        .as_ref()
+        .ok_or_else(|| syn::Error::new_spanned(field, NAMED_FIELDS_ONLY))
+}
+
+/// Replace each occurrence of the `Self` type in the given tokens with the given type (typically
+/// the struct name).
+fn replace_self_type(tokens: TokenStream2, self_type: &impl ToTokens) -> TokenStream2 {
+    tokens
+        .into_iter()
+        .flat_map(|token| match token {
+            TokenTree::Ident(ident) if ident == "Self" => self_type.to_token_stream(),
+            TokenTree::Group(group) => {
+                let stream = replace_self_type(group.stream(), self_type);
+                let mut new_group = Group::new(group.delimiter(), stream);
+                new_group.set_span(group.span());
+                TokenTree::Group(new_group).into()
+            }
+            other => other.into(),
+        })
+        .collect()
+}
+
+/// Generates a `HasTargetLenses` impl for the given lens, which allows `lens!` to reach into the
+/// target of the lens.  With `#[lens(remote = "TypeDef")]` on the field, these are the lenses of
+/// the given type (which restates the fields of the foreign field type); otherwise they are the
+/// lenses of the field type itself.  In the latter case the bound is higher-ranked (just like the
+/// `Clone` bound on `ValueLens`), so that the impl is only usable if the field type implements
+/// `HasLenses`, rather than being rejected outright for leaf field types.
+fn target_lenses_impl(
+    lens_name: &Ident,
+    generics: &Generics,
+    field_options: &FieldOptions,
+    field_type: &TokenStream2,
+) -> TokenStream2 {
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_predicates = where_predicates(generics);
+    let (lenses_type, has_lenses_predicate) = match &field_options.remote {
+        Some(remote) => (remote.to_token_stream(), quote!()),
+        None => (
+            field_type.clone(),
+            quote!(for<'__lens> #field_type: pl_lens::HasLenses),
+        ),
+    };
+    quote!(
+        #[allow(dead_code)]
+        impl #impl_generics pl_lens::HasTargetLenses for #lens_name #ty_generics
+        where
+            #where_predicates
+            #has_lenses_predicate
+        {
+            type TargetLenses = <#lenses_type as pl_lens::HasLenses>::Lenses;
+
+            #[inline(always)]
+            fn target_lenses(&self) -> Self::TargetLenses {
+                <#lenses_type as pl_lens::HasLenses>::lenses()
+            }
+        }
+    )
+}
+
 // XXX: Lifted from librustc_lint/builtin.rs
 fn to_camel_case(s: &str) -> String {
     s.split('_')
@@ -243,12 +1040,221 @@ fn to_camel_case(s: &str) -> String {
         .concat()
 }
 
+/// Convert a field name (which is expected to be in snake case) to the screaming snake case
+/// used for constants, dropping the `r#` prefix of a raw identifier.
+fn to_screaming_snake_case(s: &str) -> String {
+    s.trim_start_matches("r#").to_uppercase()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use syn::parse::Parser;
+    use syn::parse_quote;
+
+    fn derive_error(input: DeriveInput) -> String {
+        match derive_lenses(&input) {
+            Ok(_) => panic!("expected `derive_lenses` to fail"),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn deriving_for_a_union_should_fail() {
+        let input = parse_quote!(
+            union Bits {
+                int: u32,
+                float: f32,
+            }
+        );
+        assert_eq!(derive_error(input), STRUCTS_ONLY);
+    }
+
+    #[test]
+    fn deriving_for_a_tuple_struct_should_work() {
+        let input = parse_quote!(
+            struct Pair(u32, u32);
+        );
+        assert!(derive_lenses(&input).is_ok());
+    }
+
+    #[test]
+    fn deriving_for_a_unit_struct_should_fail() {
+        let input = parse_quote!(
+            struct Marker;
+        );
+        assert_eq!(derive_error(input), UNIT_STRUCTS_UNSUPPORTED);
+    }
+
+    #[test]
+    fn deriving_for_a_generic_struct_should_work() {
+        let input = parse_quote!(
+            #[lens(methods)]
+            struct Page<'a, P: Clone, const N: usize>
+            where
+                P: Default,
+            {
+                items: [&'a P; N],
+            }
+        );
+        assert!(derive_lenses(&input).is_ok());
+    }
+
+    #[test]
+    fn deriving_for_a_generic_enum_should_fail() {
+        let input = parse_quote!(
+            enum Either<L, R> {
+                Left(L),
+                Right(R),
+            }
+        );
+        assert_eq!(derive_error(input), GENERIC_ENUMS_UNSUPPORTED);
+    }
+
+    #[test]
+    fn deriving_for_a_generic_tuple_struct_should_succeed() {
+        let input = parse_quote!(
+            struct Pair<A, B: Clone>(A, B)
+            where
+                A: Default;
+        );
+        assert!(derive_lenses(&input).is_ok());
+
+        let input = parse_quote!(
+            struct Wrapper<T>(T);
+        );
+        assert!(derive_lenses(&input).is_ok());
+    }
+
+    #[test]
+    fn deriving_a_remote_enum_should_fail() {
+        let input = parse_quote!(
+            #[lens(remote = "other::Either")]
+            enum EitherDef {
+                Left(u32),
+                Right(u32),
+            }
+        );
+        assert_eq!(derive_error(input), REMOTE_NAMED_FIELDS_ONLY);
+    }
+
+    #[test]
+    fn deriving_field_markers_for_a_newtype_should_fail() {
+        let input = parse_quote!(
+            #[lens(fields = "crate::fields")]
+            struct UserId(u64);
+        );
+        assert_eq!(derive_error(input), FIELDS_NAMED_FIELDS_ONLY);
+    }
+
+    #[test]
+    fn deriving_a_field_marker_without_a_marker_module_should_fail() {
+        let input = parse_quote!(
+            struct Document {
+                #[lens(field)]
+                updated_at: u64,
+            }
+        );
+        assert_eq!(derive_error(input), FIELD_REQUIRES_FIELDS);
+    }
+
+    #[test]
+    fn deriving_a_field_marker_for_an_enum_field_should_fail() {
+        let input = parse_quote!(
+            enum Event {
+                Created {
+                    #[lens(field)]
+                    updated_at: u64,
+                },
+            }
+        );
+        assert_eq!(derive_error(input), FIELD_NAMED_FIELDS_ONLY);
+    }
+
+    #[test]
+    fn deriving_accessors_for_an_enum_field_should_fail() {
+        let input = parse_quote!(
+            enum Endpoint {
+                Tcp {
+                    #[lens(get = "port", set = "set_port")]
+                    port: u16,
+                },
+            }
+        );
+        assert_eq!(derive_error(input), ACCESSORS_STRUCTS_ONLY);
+    }
+
+    #[test]
+    fn deriving_consts_for_a_tuple_struct_should_fail() {
+        let input = parse_quote!(
+            #[lens(consts)]
+            struct Pair(u32, u32);
+        );
+        assert_eq!(derive_error(input), CONSTS_NAMED_FIELDS_ONLY);
+    }
+
+    #[test]
+    fn deriving_consts_with_the_same_name_should_fail() {
+        let input = parse_quote!(
+            #[lens(consts)]
+            struct Links {
+                url: String,
+                URL: String,
+            }
+        );
+        assert_eq!(
+            derive_error(input),
+            "the lens constants for the fields `url` and `URL` would both be named `URL`"
+        );
+    }
+
+    #[test]
+    fn deriving_remote_update_methods_should_fail() {
+        let input = parse_quote!(
+            #[lens(remote = "std::ops::Range<u32>", methods)]
+            struct RangeDef {
+                start: u32,
+                end: u32,
+            }
+        );
+        assert_eq!(derive_error(input), METHODS_NOT_REMOTE);
+    }
+
+    #[test]
+    fn the_name_of_an_unnamed_field_should_be_an_error() {
+        // (The derive only asks for the names of fields in structs and struct variants with
+        // named fields, so this can't be reached from user code)
+        let field = Field::parse_unnamed.parse2(quote!(u32)).unwrap();
+        let error = field_ident(&field).unwrap_err();
+        assert_eq!(error.to_string(), NAMED_FIELDS_ONLY);
+    }
+
+    #[test]
+    fn replace_self_type_should_work() {
+        let struct_name = format_ident!("Node");
+        let ty = quote!(Option<Box<Self>>);
+        assert_eq!(
+            replace_self_type(ty, &struct_name).to_string(),
+            quote!(Option<Box<Node>>).to_string()
+        );
+        let ty = quote!(HashMap<String, Vec<(Self, u32)>>);
+        assert_eq!(
+            replace_self_type(ty, &struct_name).to_string(),
+            quote!(HashMap<String, Vec<(Node, u32)>>).to_string()
+        );
+    }
 
     #[test]
     fn to_camel_case_should_work() {
         assert_eq!(to_camel_case("this_is_snake_case"), "ThisIsSnakeCase");
     }
+
+    #[test]
+    fn to_screaming_snake_case_should_work() {
+        assert_eq!(
+            to_screaming_snake_case("this_is_snake_case"),
+            "THIS_IS_SNAKE_CASE"
+        );
+        assert_eq!(to_screaming_snake_case("r#type"), "TYPE");
+    }
 }
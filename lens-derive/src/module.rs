@@ -0,0 +1,156 @@
+//
+// Copyright (c) 2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{parse_quote, Data, DeriveInput, Ident, Visibility};
+
+use crate::attrs::ModuleName;
+
+/// Return a copy of the given input in which the visibility of the type and of each of its fields
+/// has been adjusted so that it has the same meaning when the generated items are declared in a
+/// child module (see `wrap_in_module`).
+pub(crate) fn input_for_module(input: &DeriveInput) -> DeriveInput {
+    let mut input = input.clone();
+    input.vis = nested_visibility(&input.vis);
+    match &mut input.data {
+        Data::Struct(data_struct) => {
+            for field in data_struct.fields.iter_mut() {
+                field.vis = nested_visibility(&field.vis);
+            }
+        }
+        Data::Enum(data_enum) => {
+            for field in data_enum
+                .variants
+                .iter_mut()
+                .flat_map(|variant| variant.fields.iter_mut())
+            {
+                field.vis = nested_visibility(&field.vis);
+            }
+        }
+        Data::Union(_) => {}
+    }
+    input
+}
+
+/// Declare the given items in a child module of the module that contains the type, for example:
+///     pub mod person_lenses {
+///         use super::*;
+///         pub struct PersonNameLens;
+///         ...
+///     }
+/// The module shares the visibility of the type, and it imports everything from the parent
+/// module so that the field types resolve just as they do in the type declaration.
+pub(crate) fn wrap_in_module(
+    input: &DeriveInput,
+    module_name: &ModuleName,
+    items: TokenStream2,
+) -> TokenStream2 {
+    let type_name = &input.ident;
+    let module_visibility = &input.vis;
+    let module_ident = match module_name {
+        ModuleName::Default(span) => format_ident!(
+            "{}_lenses",
+            to_snake_case(&type_name.unraw().to_string()),
+            span = *span
+        ),
+        ModuleName::Explicit(ident) => ident.clone(),
+    };
+    let module_doc = format!("The lenses for `{}`.", type_name);
+    quote!(
+        #[doc = #module_doc]
+        #module_visibility mod #module_ident {
+            #[allow(unused_imports)]
+            use super::*;
+
+            #items
+        }
+    )
+}
+
+/// Return the visibility that, when used in a child module, is equivalent to the given
+/// visibility in the parent module.
+fn nested_visibility(vis: &Visibility) -> Visibility {
+    match vis {
+        // Private items are visible throughout the parent module
+        Visibility::Inherited => parse_quote!(pub(super)),
+        Visibility::Restricted(restricted) => {
+            let path = &restricted.path;
+            let first: &Ident = &path.segments[0].ident;
+            if first == "crate" {
+                // Crate-relative paths mean the same thing everywhere
+                vis.clone()
+            } else if first == "self" && path.segments.len() == 1 {
+                // `self` in the parent is `super` in the child
+                parse_quote!(pub(super))
+            } else if first == "self" {
+                let rest = path.segments.iter().skip(1);
+                parse_quote!(pub(in super #(::#rest)*))
+            } else {
+                // `super` (or any other relative path) needs one more level of `super`
+                parse_quote!(pub(in super::#path))
+            }
+        }
+        Visibility::Public(_) | Visibility::Crate(_) => vis.clone(),
+    }
+}
+
+/// Convert a type name (which is expected to be in camel case) to snake case.
+fn to_snake_case(s: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in s.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nested(vis: Visibility) -> String {
+        let vis = nested_visibility(&vis);
+        quote!(#vis).to_string()
+    }
+
+    #[test]
+    fn nested_visibility_should_work() {
+        assert_eq!(
+            nested(Visibility::Inherited),
+            quote!(pub(super)).to_string()
+        );
+        assert_eq!(nested(parse_quote!(pub)), quote!(pub).to_string());
+        assert_eq!(
+            nested(parse_quote!(pub(crate))),
+            quote!(pub(crate)).to_string()
+        );
+        assert_eq!(
+            nested(parse_quote!(pub(self))),
+            quote!(pub(super)).to_string()
+        );
+        assert_eq!(
+            nested(parse_quote!(pub(super))),
+            quote!(pub(in super::super)).to_string()
+        );
+        assert_eq!(
+            nested(parse_quote!(pub(in self::model))),
+            quote!(pub(in super::model)).to_string()
+        );
+    }
+
+    #[test]
+    fn to_snake_case_should_work() {
+        assert_eq!(to_snake_case("UserProfile"), "user_profile");
+        assert_eq!(to_snake_case("Person"), "person");
+    }
+}
@@ -0,0 +1,121 @@
+//
+// Copyright (c) 2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{DeriveInput, Field};
+
+use crate::attrs::parse_field_options;
+use crate::{
+    check_no_struct_field_options, field_lens_impls, lens_struct, lens_value, replace_self_type,
+    target_lenses_impl, where_predicates, FieldAccess,
+};
+
+/// Generates an `Iso` between the given newtype (a tuple struct with a single field) and the
+/// type that it wraps.  The iso is also a `Lens` that focuses on the wrapped field.  The iso type
+/// for a generic newtype has the same generic parameters as the newtype.
+pub(crate) fn derive_newtype_lenses(
+    input: &DeriveInput,
+    field: &Field,
+) -> syn::Result<TokenStream2> {
+    // Extract the struct name and generics
+    let struct_name = &input.ident;
+    let generics = &input.generics;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_predicates = where_predicates(generics);
+    let source_type = quote!(#struct_name #ty_generics);
+
+    // The lens structs are declared alongside the struct, so they share its visibility
+    let lens_visibility = &input.vis;
+
+    // Note that the field type is used within the impls below, where `Self` would refer to
+    // the iso type, so we replace any `Self` with the struct name
+    let field_type = &field.ty;
+    let field_type = replace_self_type(quote!(#field_type), &source_type);
+
+    // Build the Iso name from the struct name (for example, "StructIso")
+    let iso_name = format_ident!("{}Iso", struct_name);
+    let field_options = parse_field_options(&field.attrs)?;
+    check_no_struct_field_options(&field_options)?;
+    let iso_type = quote!(#iso_name #ty_generics);
+    let target_lenses = target_lenses_impl(&iso_name, generics, &field_options, &field_type);
+
+    let iso_struct = lens_struct(lens_visibility, &iso_name, generics, &source_type);
+    let lens_impls = field_lens_impls(
+        &iso_type,
+        generics,
+        &source_type,
+        &field_type,
+        0,
+        &FieldAccess::Member(quote!(0)),
+    );
+
+    let iso_items = quote!(
+        // Include the iso struct declaration
+        #iso_struct
+
+        // Include the `Lens`, `RefLens` and `ValueLens` impls
+        #lens_impls
+
+        // Include the `Iso` impl
+        #[allow(dead_code)]
+        impl #impl_generics pl_lens::Iso for #iso_type
+        where
+            #where_predicates
+        {
+            #[inline(always)]
+            fn to_target(&self, source: #source_type) -> #field_type {
+                source.0
+            }
+
+            #[inline(always)]
+            fn to_source(&self, target: #field_type) -> #source_type {
+                #struct_name(target)
+            }
+        }
+
+        // Include the `HasTargetLenses` impl (only usable if the target has lenses)
+        #target_lenses
+    );
+
+    // Build a `<StructName>Lenses` tuple struct that holds the iso in the position of the
+    // wrapped field, for example:
+    //     struct UserIdLenses(UserIdIso);
+    let lenses_struct_name = format_ident!("{}Lenses", struct_name);
+    let lenses_type = quote!(#lenses_struct_name #ty_generics);
+    let lenses_struct = quote!(
+        #[allow(dead_code)]
+        #[doc(hidden)]
+        #lens_visibility struct #lenses_struct_name #generics (#lens_visibility #iso_type)
+        where
+            #where_predicates;
+    );
+
+    // Implement `HasLenses` for the struct so that the iso can be resolved from the struct type
+    let iso_value = lens_value(&iso_name, generics);
+    let has_lenses_impl = quote!(
+        #[allow(dead_code)]
+        impl #impl_generics pl_lens::HasLenses for #source_type
+        where
+            #where_predicates
+        {
+            type Lenses = #lenses_type;
+
+            #[inline(always)]
+            fn lenses() -> #lenses_type {
+                #lenses_struct_name(#iso_value)
+            }
+        }
+    );
+
+    // Build the output
+    Ok(quote! {
+        #iso_items
+
+        #lenses_struct
+
+        #has_lenses_impl
+    })
+}
@@ -0,0 +1,121 @@
+//
+// Copyright (c) 2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::punctuated::Punctuated;
+use syn::token::Comma;
+use syn::{DeriveInput, Field, Index};
+
+use crate::attrs::parse_field_options;
+use crate::{
+    check_no_struct_field_options, field_lens_impls, lens_struct, lens_value, replace_self_type,
+    target_lenses_impl, where_predicates, FieldAccess,
+};
+
+/// Generates a `Lens` for each field of the given tuple struct (a struct with more than one
+/// unnamed field), named after the position of the field (for example, "Pair0Lens").  The lens
+/// types for a generic tuple struct have the same generic parameters as the struct.
+pub(crate) fn derive_tuple_struct_lenses(
+    input: &DeriveInput,
+    fields: &Punctuated<Field, Comma>,
+) -> syn::Result<TokenStream2> {
+    // Extract the struct name and generics
+    let struct_name = &input.ident;
+    let generics = &input.generics;
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+    let where_predicates = where_predicates(generics);
+    let source_type = quote!(#struct_name #ty_generics);
+
+    // The lens structs are declared alongside the struct, so they share its visibility
+    let lens_visibility = &input.vis;
+
+    // Generate lenses for each field in the struct
+    let lens_names = (0..fields.len())
+        .map(|index| format_ident!("{}{}Lens", struct_name, index))
+        .collect::<Vec<_>>();
+    let lens_items = fields
+        .iter()
+        .zip(&lens_names)
+        .enumerate()
+        .map(|(index, (field, lens_name))| {
+            let field_index = Index::from(index);
+
+            // Note that the field type is used within the lens impls below, where `Self` would
+            // refer to the lens type, so we replace any `Self` with the struct name
+            let field_type = &field.ty;
+            let field_type = replace_self_type(quote!(#field_type), &source_type);
+
+            let field_options = parse_field_options(&field.attrs)?;
+            check_no_struct_field_options(&field_options)?;
+            let target_lenses =
+                target_lenses_impl(lens_name, generics, &field_options, &field_type);
+
+            let lens_struct = lens_struct(lens_visibility, lens_name, generics, &source_type);
+            let lens_impls = field_lens_impls(
+                &quote!(#lens_name #ty_generics),
+                generics,
+                &source_type,
+                &field_type,
+                index as u64,
+                &FieldAccess::Member(quote!(#field_index)),
+            );
+
+            Ok(quote!(
+                // Include the lens struct declaration
+                #lens_struct
+
+                // Include the `Lens`, `RefLens` and `ValueLens` impls
+                #lens_impls
+
+                // Include the `HasTargetLenses` impl (only usable if the target has lenses)
+                #target_lenses
+            ))
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    // Build a `<StructName>Lenses` tuple struct that holds the lens for each field in the
+    // position of that field, so that (for example) `lens!(Pair.0)` works:
+    //     struct PairLenses(Pair0Lens, Pair1Lens);
+    let lenses_struct_name = format_ident!("{}Lenses", struct_name);
+    let lenses_type = quote!(#lenses_struct_name #ty_generics);
+    let lenses_struct = quote!(
+        #[allow(dead_code)]
+        #[doc(hidden)]
+        #lens_visibility struct #lenses_struct_name #generics (
+            #(#lens_visibility #lens_names #ty_generics),*
+        )
+        where
+            #where_predicates;
+    );
+
+    // Implement `HasLenses` for the struct so that the lenses can be resolved from the struct type
+    let lens_values = lens_names
+        .iter()
+        .map(|lens_name| lens_value(lens_name, generics));
+    let has_lenses_impl = quote!(
+        #[allow(dead_code)]
+        impl #impl_generics pl_lens::HasLenses for #source_type
+        where
+            #where_predicates
+        {
+            type Lenses = #lenses_type;
+
+            #[inline(always)]
+            fn lenses() -> #lenses_type {
+                #lenses_struct_name(#(#lens_values),*)
+            }
+        }
+    );
+
+    // Build the output
+    Ok(quote! {
+        #(#lens_items)*
+
+        #lenses_struct
+
+        #has_lenses_impl
+    })
+}
@@ -6,121 +6,713 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
+use proc_macro2::{Delimiter, Group, Literal, Span, TokenStream as TokenStream2, TokenTree};
 use proc_macro_hack::proc_macro_hack;
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, quote_spanned};
+use syn::ext::IdentExt;
+use syn::parse::{ParseStream, Parser};
 use syn::spanned::Spanned;
-use syn::{parse_macro_input, Expr, ExprField, Member};
+use syn::{BinOp, Expr, ExprLit, Ident, Lit, Member, Token, TypePath};
 
 #[proc_macro_hack]
 pub fn lens(input: TokenStream) -> TokenStream {
-    // Parse the input tokens into a syntax tree
-    let expr = parse_macro_input!(input as Expr);
-
-    // Check that the expression is a "named struct field access"
-    let lens_parts: Vec<String>;
-    if let Expr::Field(field_access) = &expr {
-        // Extract the list of lens names
-        match extract_lens_parts(&field_access) {
-            Ok(parts) => {
-                lens_parts = parts;
-            }
-            Err(error) => {
-                return error.to_compile_error().into();
-            }
+    // Parse the input tokens (after replacing each `[*]`, which is not a valid expression by
+    // itself, with a marker), checking that they are a chain of field accesses (and indexing
+    // operations or `?`), and extract the root type and the list of segments
+    let input = replace_wildcards(TokenStream2::from(input));
+    let lens_parts = match parse_lens_parts.parse2(input) {
+        Ok(parts) => parts,
+        Err(error) => {
+            return error.to_compile_error().into();
         }
-    } else {
-        return syn::Error::new(expr.span(), "lens!() expression must be structured like a field access, e.g. `Struct.outer_field.inner_field`").to_compile_error().into();
-    }
-
-    // At this point we should have at least two parts: the root struct name
-    // and the first field name
-    if lens_parts.len() < 2 {
-        return syn::Error::new(expr.span(), "lens!() expression must be structured like a field access, e.g. `Struct.outer_field.inner_field`").to_compile_error().into();
-    }
+    };
 
-    // We can build up the composed lens by concatenating the parts of the
-    // expression (inserting `Lenses` or `_lenses` as needed); this relies
-    // on the fact that the `#derive(Lenses)` macro creates a special
-    // `struct FooLenses` for each source struct that enumerates the
-    // lens type name for each field.
+    // We can build up the composed lens by looking up the lens for each field
+    // in the set of lenses for the struct that contains it; this relies on the
+    // fact that the `#derive(Lenses)` macro creates a special `struct FooLenses`
+    // for each source struct that enumerates the lens type name for each field,
+    // and implements `HasLenses` so that the lenses for the root struct can be
+    // resolved from its type, and the lenses for a nested struct can be resolved
+    // from the lens that precedes it (via `HasTargetLenses`, which usually defers
+    // to the `HasLenses` impl for the target type of that lens).  Since everything
+    // is resolved through the type, the root may be any path to the type (for
+    // example, `crate::model::Struct3`), a type alias, or a generic type with its
+    // type arguments (for example, `Page<User>` or `Wrapper::<u32>`, which is
+    // parsed as a type, since it's not a valid expression by itself).  Tuple structs and tuples
+    // have a tuple of lenses instead, so a positional member (e.g. `Pair.0`) maps
+    // to the lens in that position.
+    //
+    // An index segment (e.g. `[i]`) instead maps to the lens over the element at
+    // that index (or key) of the target of the preceding lens.  An index that is a
+    // reference or a string literal (e.g. `[&key]` or `["PATH"]`) is a borrowed
+    // map key, and otherwise the lens is resolved from the type of the index (via
+    // `HasIndexLens`, e.g. a `usize` for a `Vec`, or the key type for a map).
     //
-    // For example, suppose we have the following lens expression:
-    //     lens!(Struct3.struct2.struct1.int32)
+    // A `?` segment (e.g. `Struct.profile?`) maps to a `SomePrism` over the
+    // (optional) target of the preceding lens.  A variant segment (e.g.
+    // `Shape.Circle`, or the last segment of a root path such as `Shape::Circle`)
+    // is resolved just like a field, from the entry for the variant in the
+    // `struct FooLenses` for the enum, which is a prism (or, for a struct variant,
+    // an affine lens that matches the variant).  Along with each optic, we also
+    // resolve its kind (`LensKind` for a field or an index, `AffineKind` for a
+    // `?` or a variant), which comes from the `HasFieldLens` impl for a name,
+    // since a name alone doesn't say whether it is a field or a variant (e.g. a
+    // field named `URL`).  Since the value may be absent, any expression with an
+    // affine optic is composed into an affine lens instead.
+    //
+    // A `[*]` or `.each` segment maps to an `EachTraversal` over the elements (or
+    // values) of the target of the preceding lens, and any expression with such a
+    // segment is composed into a `Traversal` instead.
+    //
+    // Each lens is bound to a local, so that each index expression (which may
+    // refer to local variables) is evaluated exactly once.  For example, suppose we
+    // have the following lens expression:
+    //     lens!(Struct3.struct2.items[i + 1].int32)
     //
     // We extracted the parts into `lens_parts` above, producing:
-    //     ["Struct3", "struct2", "struct1", "int32"]
+    //     root: Struct3
+    //     segments: [struct2, items, [i + 1], int32]
     //
     // Now we can access the lenses and compose them together:
-    //     compose_lens!(
-    //         _Struct3Lenses.struct2,
-    //         _Struct3Lenses.struct2_lenses.struct1,
-    //         _Struct3Lenses.struct2_lenses.struct1_lenses.int32
-    //     )
-    let mut parent_lenses_name = format_ident!("_{}Lenses", lens_parts[0]);
-    let mut child_field_name = format_ident!("{}", lens_parts[1]);
-    let mut base_lens_expr = quote!(#parent_lenses_name);
-    let mut lens_expr = quote!(#base_lens_expr.#child_field_name);
-    let mut lens_exprs: Vec<TokenStream2> = vec![lens_expr.clone()];
-
-    for lens_part in lens_parts.iter().skip(2) {
-        let prev_base_lens_expr = base_lens_expr.clone();
-        let prev_child_field_name = child_field_name.clone();
-        parent_lenses_name = format_ident!("{}_lenses", prev_child_field_name);
-        child_field_name = format_ident!("{}", lens_part);
-        base_lens_expr = quote!(#prev_base_lens_expr.#parent_lenses_name);
-        lens_expr = quote!(#base_lens_expr.#child_field_name);
-        lens_exprs.push(lens_expr.clone());
-    }
+    //     {
+    //         let (lens0, kind0) = source_lenses(PhantomData::<Struct3>).struct2;
+    //         let (lens1, kind1) = target_lenses(&lens0).items;
+    //         let (lens2, kind2) = (index_lens(&lens1, i + 1), LensKind);
+    //         let (lens3, kind3) = target_lenses(&lens2).int32;
+    //         let kind = join_kinds(join_kinds(join_kinds(kind0, kind1), kind2), kind3);
+    //         compose_kind(kind, lens0, compose_kind(kind, lens1, compose_kind(kind, lens2, lens3)))
+    //     }
+    //
+    // (where each lookup by name actually goes through `HasFieldLens`, as described in
+    // `named_lens_binding`).
+    let expanded = lens_expr(&Root::Type(&lens_parts.root), &lens_parts.segments);
+
+    // Hand the output tokens back to the compiler
+    TokenStream::from(expanded)
+}
+
+#[proc_macro_hack]
+pub fn update(input: TokenStream) -> TokenStream {
+    // Parse the input tokens (after replacing each `[*]` as for `lens!`) into the value to update
+    // and the list of edits, each of which has a path that is parsed just like the segments of a
+    // `lens!` expression
+    let input = replace_wildcards(TokenStream2::from(input));
+    let update_parts = match parse_update_parts.parse2(input) {
+        Ok(parts) => parts,
+        Err(error) => {
+            return error.to_compile_error().into();
+        }
+    };
 
-    // Build the output
+    // The lenses for the first segment of each path are resolved from the type of the value
+    // (via a marker for that type, which is taken from the one mutable borrow of the value), and
+    // otherwise each path is composed into an optic just like a `lens!` expression.  The
+    // right-hand side of each assignment (or compound assignment) is evaluated up front, before
+    // the value is borrowed, so that it can refer to the value as it was before the update; then
+    // the optics (and any closures) are evaluated, and each edit is applied in turn.
+    //
+    // An assignment sets the target through the optic (so that it goes through the setter of a
+    // lens with accessor methods), a compound assignment (e.g. `+=`) applies the operator to the
+    // target, and a closure is called with a reference to the target, which is replaced with the
+    // result.  The latter two edit the target in place if the optic has `AffineRefLens`, and
+    // otherwise get the target, edit it, and set it back (via `AffineEdit`).  For a `Traversal`,
+    // each edit is applied to every target (cloning the right-hand side for each one), and for an
+    // affine lens, to the target if it is present.
+    //
+    // For example, suppose we have the following update expression:
+    //     update!(person, address.street = s, age += 1, name = |n| n.to_uppercase())
+    //
+    // This produces:
+    //     {
+    //         use pl_lens::{EditByValue as _, EditInPlace as _};
+    //         let mut value0 = s;
+    //         let mut value1 = 1;
+    //         let source = &mut person;
+    //         let root = source_type(&*source);
+    //         let lens0 = { let (lens0, kind0) = source_lenses(root).address; ... };
+    //         let lens1 = { let (lens0, kind0) = source_lenses(root).age; lens0 };
+    //         let lens2 = { let (lens0, kind0) = source_lenses(root).name; lens0 };
+    //         let mut value2 = target_fn(affine_target_type(&lens2), |n| n.to_uppercase());
+    //         lens0.try_mutate(&mut *source, value0);
+    //         (&AffineEdit(&lens1)).try_edit(&mut *source, |target| { *target += value1; });
+    //         (&AffineEdit(&lens2)).try_edit(&mut *source, |target| { *target = value2(&*target); });
+    //     }
+    let value = &update_parts.value;
+    let root = Ident::new("root", Span::mixed_site());
+    let source = Ident::new("source", Span::mixed_site());
+    let target = Ident::new("target", Span::mixed_site());
+    let mut values: Vec<TokenStream2> = vec![];
+    let mut bindings: Vec<TokenStream2> = vec![];
+    let mut applications: Vec<TokenStream2> = vec![];
+    for (position, edit) in update_parts.edits.iter().enumerate() {
+        // Errors about the right-hand side (e.g. a mismatched type) point at the right-hand side
+        let span = edit.value().span();
+        let lens_name = format_ident!("lens{}", position, span = Span::mixed_site());
+        let value_name = format_ident!(
+            "value{}",
+            position,
+            span = Span::mixed_site().located_at(span)
+        );
+        let is_traversal = is_traversal(&edit.segments);
+        let lens = lens_expr(&Root::Marker(&root), &edit.segments);
+        bindings.push(quote!(let #lens_name = #lens;));
+        match &edit.op {
+            EditOp::Set(value) | EditOp::Compound(_, value) => values.push(quote_spanned!(span=>
+                #[allow(unused_mut)]
+                let mut #value_name = #value;
+            )),
+            EditOp::Modify(f) => {
+                let target_type = if is_traversal {
+                    quote!(target_type)
+                } else {
+                    quote!(affine_target_type)
+                };
+                bindings.push(quote_spanned!(span=>
+                    #[allow(unused_mut)]
+                    let mut #value_name =
+                        pl_lens::target_fn(pl_lens::#target_type(&#lens_name), #f);
+                ));
+            }
+        }
+
+        // Each target of a traversal gets its own clone of the right-hand side
+        let operand = if is_traversal {
+            quote_spanned!(span=> ::std::clone::Clone::clone(&#value_name))
+        } else {
+            quote!(#value_name)
+        };
+        let statement = match &edit.op {
+            EditOp::Set(_) => quote_spanned!(span=> *#target = #operand),
+            EditOp::Compound(op, _) => quote_spanned!(span=> *#target #op #operand),
+            EditOp::Modify(_) => quote_spanned!(span=> *#target = #value_name(&*#target)),
+        };
+        applications.push(match &edit.op {
+            _ if is_traversal => quote_spanned!(span=>
+                pl_lens::Traversal::for_each_mut(&#lens_name, &mut *#source, &mut |#target| {
+                    #statement;
+                });
+            ),
+            EditOp::Set(_) => quote_spanned!(span=>
+                pl_lens::AffineLens::try_mutate(&#lens_name, &mut *#source, #value_name);
+            ),
+            EditOp::Compound(..) | EditOp::Modify(_) => quote_spanned!(span=>
+                (&pl_lens::AffineEdit(&#lens_name)).try_edit(&mut *#source, |#target| {
+                    #statement;
+                });
+            ),
+        });
+    }
     let expanded = quote! {
-        pl_lens::compose_lens!(#(#lens_exprs),*);
+        {
+            #[allow(unused_imports)]
+            use pl_lens::{EditByValue as _, EditInPlace as _};
+            #(#values)*
+            let #source = &mut #value;
+            let #root = pl_lens::source_type(&*#source);
+            #(#bindings)*
+            #(#applications)*
+        }
     };
 
     // Hand the output tokens back to the compiler
     TokenStream::from(expanded)
 }
 
-/// Given an expression like `Struct1.struct2_field.struct3_field`, recurse until we hit the root
-/// struct and then build a list of lens names that can be passed to `compose_lens!`.
-/// For example, the above expression would result in the following list of identifiers:
+/// Where the lenses for the first segment of a lens expression come from.
+enum Root<'a> {
+    /// The root type, e.g. `Struct1` in `lens!(Struct1.field)`.
+    Type(&'a TypePath),
+
+    /// A local holding a marker (a `PhantomData`) for the type of the value being updated by an
+    /// `update!` expression.
+    Marker(&'a Ident),
+}
+
+/// Returns the expression for the lens (or affine lens, or traversal) that is composed from the
+/// given segments, starting from the given root.
+fn lens_expr(root: &Root, segments: &[Segment]) -> TokenStream2 {
+    let mut lens_bindings: Vec<TokenStream2> = vec![];
+    let mut lens_names: Vec<Ident> = vec![];
+    let mut kind_names: Vec<Ident> = vec![];
+    for (position, segment) in segments.iter().enumerate() {
+        let lens_name = format_ident!("lens{}", position, span = Span::mixed_site());
+        let kind_name = format_ident!("kind{}", position, span = Span::mixed_site());
+        // Each step is spanned to its segment, so that any error (e.g. an unknown field, or an
+        // attempt to reach into a type without lenses) points at that segment; this includes
+        // the reference to the preceding lens, which is otherwise spanned to the whole macro
+        let span = segment.span();
+        let prev_lens_name = lens_names
+            .last()
+            .map(|name| Ident::new(&name.to_string(), Span::mixed_site().located_at(span)));
+
+        // The lenses that a field or variant segment is looked up in, along with a marker for
+        // the type that should have the field or variant (for error reporting)
+        let (lenses, source) = match &prev_lens_name {
+            None => match root {
+                // A root type without lenses is reported once, against the root type (rather
+                // than again against the first segment)
+                Root::Type(root) => (
+                    quote_spanned!(root.span()=>
+                        pl_lens::source_lenses(::std::marker::PhantomData::<#root>)
+                    ),
+                    quote_spanned!(span=> ::std::marker::PhantomData::<#root>),
+                ),
+                Root::Marker(marker) => (
+                    quote_spanned!(span=> pl_lens::source_lenses(#marker)),
+                    quote_spanned!(span=> #marker),
+                ),
+            },
+            Some(prev_lens_name) => (
+                quote_spanned!(span=> pl_lens::target_lenses(&#prev_lens_name)),
+                quote_spanned!(span=> pl_lens::target_type(&#prev_lens_name)),
+            ),
+        };
+        let lens_binding = match (segment, &prev_lens_name) {
+            (Segment::Field(Member::Named(name)), _) => named_lens_binding(
+                &lens_name, &kind_name, position, name, lenses, source, false,
+            ),
+            (Segment::Variant(name), _) => {
+                named_lens_binding(&lens_name, &kind_name, position, name, lenses, source, true)
+            }
+            (Segment::Field(member), _) => {
+                quote_spanned!(span=>
+                    let #lens_name = #lenses.#member;
+                    let #kind_name = pl_lens::LensKind;
+                )
+            }
+            (Segment::Index(index), Some(prev_lens_name)) => {
+                let lens_fn = if is_borrowed_key(index) {
+                    quote!(key_ref_lens)
+                } else {
+                    quote!(index_lens)
+                };
+                quote_spanned!(span=>
+                    let #lens_name = pl_lens::#lens_fn(&#prev_lens_name, #index);
+                    let #kind_name = pl_lens::LensKind;
+                )
+            }
+            (Segment::Some(_), Some(prev_lens_name)) => {
+                quote_spanned!(span=>
+                    let #lens_name = pl_lens::some_prism(&#prev_lens_name);
+                    let #kind_name = pl_lens::AffineKind;
+                )
+            }
+            (Segment::Each(_), Some(prev_lens_name)) => {
+                quote_spanned!(span=>
+                    let #lens_name = pl_lens::each_traversal(&#prev_lens_name);
+                    let #kind_name = pl_lens::TraversalKind;
+                )
+            }
+            (Segment::Index(_), None) | (Segment::Some(_), None) | (Segment::Each(_), None) => {
+                unreachable!("the first segment is always a field")
+            }
+        };
+        lens_bindings.push(lens_binding);
+        lens_names.push(lens_name);
+        kind_names.push(kind_name);
+    }
+
+    // Build the output, which composes the optics according to the kind that results from
+    // joining the kinds of all of them (e.g. an affine lens, if any of them is affine), nesting
+    // to the right just like `compose_lens!`
+    let kind = Ident::new("kind", Span::mixed_site());
+    let mut lens_names = lens_names.into_iter().rev();
+    let last_lens_name = lens_names
+        .next()
+        .expect("a lens expression has at least one segment");
+    let composed = lens_names.fold(
+        quote!(#last_lens_name),
+        |composed, lens_name| quote!(pl_lens::compose_kind(#kind, #lens_name, #composed)),
+    );
+    let mut kind_names = kind_names.into_iter();
+    let first_kind_name = kind_names
+        .next()
+        .expect("a lens expression has at least one segment");
+    let joined_kind = kind_names.fold(
+        quote!(#first_kind_name),
+        |joined, kind_name| quote!(pl_lens::join_kinds(#joined, #kind_name)),
+    );
+    quote! {
+        {
+            #(#lens_bindings)*
+            #[allow(unused_variables)]
+            let #kind = #joined_kind;
+            #composed
+        }
+    }
+}
+
+/// Return true if the given segments include a `[*]` or `.each`, in which case the composed optic
+/// is a `Traversal` (rather than a lens or an affine lens).
+fn is_traversal(segments: &[Segment]) -> bool {
+    segments
+        .iter()
+        .any(|segment| matches!(segment, Segment::Each(_)))
+}
+
+/// Returns the bindings for the optic for the field (or variant) of the given name, and for its
+/// kind, which are looked up in the given lenses by name via `HasFieldLens` (or for a name that
+/// looks like a variant, via `HasVariantLens`, which only differs in how an unknown name is
+/// reported), rather than with a plain field access.  This way, if there is no such field, the
+/// error is reported in terms of the given source type (e.g. "no field `adress` on `Person`"),
+/// and points at the segment.  The type that names the field in the error is declared in a block
+/// of its own, so that it can't shadow anything else in the expression.
+fn named_lens_binding(
+    lens_name: &Ident,
+    kind_name: &Ident,
+    position: usize,
+    name: &Ident,
+    lenses: TokenStream2,
+    source: TokenStream2,
+    is_variant: bool,
+) -> TokenStream2 {
+    let span = Span::mixed_site().located_at(name.span());
+    let lenses_name = format_ident!("lenses{}", position, span = span);
+    let source_name = format_ident!("source{}", position, span = span);
+    let name_type = name_type(name);
+    let (lookup_trait, lookup_fn) = if is_variant {
+        (quote!(HasVariantLens), quote!(variant_lens))
+    } else {
+        (quote!(HasFieldLens), quote!(field_lens))
+    };
+    quote_spanned!(name.span()=>
+        let #lenses_name = #lenses;
+        let #source_name = #source;
+        let (#lens_name, #kind_name) = {
+            #[allow(dead_code, non_camel_case_types)]
+            struct #name;
+            pl_lens::#lookup_trait::<#name_type, #name, _, _, _>::#lookup_fn(
+                #source_name,
+                #lenses_name,
+            )
+        };
+    )
+}
+
+/// Returns the `FieldName` type for the given field (or variant) name, which is how the
+/// `HasFieldLens` impls generated by `#[derive(Lenses)]` are keyed.  (The hash is computed by
+/// `pl_lens::field_name_hash`, just as it is for the names used by the derive.)
+fn name_type(name: &Ident) -> TokenStream2 {
+    let name = Literal::string(&name.unraw().to_string());
+    quote!(pl_lens::FieldName<{ pl_lens::field_name_hash(#name) }>)
+}
+
+/// The parts of a `lens!` expression: the path to the root type, followed by the segment to
+/// access at each step.
+struct LensParts {
+    /// The path to the root type, e.g. `Struct1`, `crate::model::Struct1`, or `Page<User>`.
+    root: TypePath,
+
+    /// Each segment to access, starting from the root.
+    segments: Vec<Segment>,
+}
+
+/// The parts of an `update!` expression: the value to update, followed by the edits to apply to
+/// it.
+struct UpdateParts {
+    /// The value to update, e.g. `person`, which must be a mutable place.
+    value: Expr,
+
+    /// Each edit to apply, in order.
+    edits: Vec<Edit>,
+}
+
+/// A single edit of an `update!` expression, e.g. `address.street = s` or `age += 1`.
+struct Edit {
+    /// Each segment of the path to the target of the edit, starting from the value.
+    segments: Vec<Segment>,
+
+    /// The change to make to the target.
+    op: EditOp,
+}
+
+/// The change made by an edit of an `update!` expression.
+enum EditOp {
+    /// An assignment of a new value, e.g. `= s`.
+    Set(Expr),
+
+    /// A compound assignment, e.g. `+= 1`.
+    Compound(BinOp, Expr),
+
+    /// An assignment of a closure, e.g. `= |n| n.to_uppercase()`, which is applied to the current
+    /// value.
+    Modify(Expr),
+}
+
+impl Edit {
+    /// Returns the right-hand side of the edit.
+    fn value(&self) -> &Expr {
+        match &self.op {
+            EditOp::Set(value) | EditOp::Compound(_, value) | EditOp::Modify(value) => value,
+        }
+    }
+}
+
+/// A single step of a `lens!` expression.
+enum Segment {
+    /// A field access, by name or by position (for tuple structs and tuples), e.g. `.name`
+    /// or `.0`.
+    Field(Member),
+
+    /// An indexing operation, e.g. `[3]` or `[row + 1]`, or a map key lookup, e.g. `[key]`,
+    /// `[&key]`, or `["PATH"]`.
+    Index(Box<Expr>),
+
+    /// An enum variant, e.g. `.Circle`, which focuses on the enum value (if it is that variant).
+    Variant(Ident),
+
+    /// A `?`, which focuses on the value inside an `Option` (if present).  The span is that of
+    /// the `?` itself.
+    Some(Span),
+
+    /// A `[*]` or `.each`, which focuses on every element of a collection (or every value of a
+    /// map).  The span is that of the `*` or `each`.
+    Each(Span),
+}
+
+impl Segment {
+    /// Returns the span of the segment, e.g. that of the field name.
+    fn span(&self) -> Span {
+        match self {
+            Segment::Field(member) => member.span(),
+            Segment::Index(index) => index.span(),
+            Segment::Variant(ident) => ident.span(),
+            Segment::Some(span) | Segment::Each(span) => *span,
+        }
+    }
+}
+
+/// The name of the marker that replaces the `*` in each `[*]` segment before parsing.
+const WILDCARD_MARKER: &str = "__pl_lens_each";
+
+/// Replace the `*` in each `[*]` segment in the given tokens with a marker identifier, so that
+/// the tokens can be parsed as an expression (where the segment looks like an ordinary index).
+fn replace_wildcards(tokens: TokenStream2) -> TokenStream2 {
+    tokens
+        .into_iter()
+        .map(|token| match &token {
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Bracket => {
+                let inner: Vec<TokenTree> = group.stream().into_iter().collect();
+                match inner.as_slice() {
+                    [TokenTree::Punct(punct)] if punct.as_char() == '*' => {
+                        let marker = Ident::new(WILDCARD_MARKER, punct.span());
+                        let mut replaced = Group::new(Delimiter::Bracket, quote!(#marker));
+                        replaced.set_span(group.span());
+                        TokenTree::Group(replaced)
+                    }
+                    _ => token,
+                }
+            }
+            _ => token,
+        })
+        .collect()
+}
+
+/// Return the `Each` segment if the given index expression is the marker for `[*]`.
+fn wildcard_segment(index: &Expr) -> Option<Segment> {
+    match index {
+        Expr::Path(path) if path.path.is_ident(WILDCARD_MARKER) => {
+            Some(Segment::Each(path.path.segments[0].ident.span()))
+        }
+        _ => None,
+    }
+}
+
+/// Return true if the given index expression is a borrowed map key, i.e., a reference (e.g.
+/// `&key`) or a string literal (e.g. `"PATH"`).
+fn is_borrowed_key(index: &Expr) -> bool {
+    matches!(
+        index,
+        Expr::Reference(_)
+            | Expr::Lit(ExprLit {
+                lit: Lit::Str(_),
+                ..
+            })
+    )
+}
+
+/// Return true if the given name looks like the name of an enum variant (rather than a field),
+/// i.e., it is capitalized.  (Other than splitting a root path such as `Msg::Update`, this only
+/// decides how an unknown name is reported; whether a segment is actually a field or a variant,
+/// and so whether its optic is a lens or an affine lens, is resolved from the type that has it.)
+fn is_variant_name(ident: &Ident) -> bool {
+    ident
+        .to_string()
+        .trim_start_matches("r#")
+        .starts_with(char::is_uppercase)
+}
+
+/// The name of the placeholder that stands in for the root type when the rest of a `lens!`
+/// expression is parsed.
+const ROOT_PLACEHOLDER: &str = "__pl_lens_root";
+
+/// Parse a `lens!` expression.  The root type is parsed as a type (so that it may have generic
+/// arguments, e.g. `Page<User>`, which would otherwise be parsed as a comparison), and the rest of
+/// the expression is parsed as an expression, with a placeholder in place of the root type.
+fn parse_lens_parts(input: ParseStream) -> syn::Result<LensParts> {
+    let root: TypePath = input.parse().map_err(|error| {
+        syn::Error::new(
+            error.span(),
+            "lens!() expression must be structured like a field access, e.g. `Struct.outer_field.inner_field`",
+        )
+    })?;
+    let rest: TokenStream2 = input.parse()?;
+    let placeholder = Ident::new(ROOT_PLACEHOLDER, root.span());
+    let expr: Expr = syn::parse2(quote!(#placeholder #rest))?;
+    extract_lens_parts(&expr, &root)
+}
+
+/// The error reported for an `update!` edit that isn't an assignment to a path.
+const EDIT_ERROR: &str = "update!() edit must assign to a field path, e.g. `outer_field.inner_field = value`, `field += 1`, or `field = |value| ...`";
+
+/// Parse an `update!` expression, i.e., the value to update followed by a comma-separated list
+/// of edits.
+fn parse_update_parts(input: ParseStream) -> syn::Result<UpdateParts> {
+    let value: Expr = input.parse()?;
+    let mut edits = vec![];
+    while !input.is_empty() {
+        input.parse::<Token![,]>()?;
+        if input.is_empty() {
+            break;
+        }
+        let edit: Expr = input.parse()?;
+        edits.push(extract_edit(&edit)?);
+    }
+    if edits.is_empty() {
+        return Err(syn::Error::new(
+            value.span(),
+            "update!() expression must have at least one edit, e.g. `update!(value, field = 1)`",
+        ));
+    }
+    Ok(UpdateParts { value, edits })
+}
+
+/// Split the given `update!` edit into its path (which is parsed like a `lens!` expression,
+/// starting from a placeholder for the root) and the change to make to its target.
+fn extract_edit(edit: &Expr) -> syn::Result<Edit> {
+    let (path, op) = match edit {
+        // (A closure can still be assigned as is by wrapping it in parentheses)
+        Expr::Assign(assign) => (
+            &*assign.left,
+            match &*assign.right {
+                Expr::Closure(_) => EditOp::Modify((*assign.right).clone()),
+                right => EditOp::Set(right.clone()),
+            },
+        ),
+        Expr::AssignOp(assign) => (
+            &*assign.left,
+            EditOp::Compound(assign.op, (*assign.right).clone()),
+        ),
+        _ => return Err(syn::Error::new(edit.span(), EDIT_ERROR)),
+    };
+    let placeholder = Ident::new(ROOT_PLACEHOLDER, path.span());
+    let root = TypePath {
+        qself: None,
+        path: placeholder.clone().into(),
+    };
+    let segments = syn::parse2(quote!(#placeholder . #path))
+        .and_then(|expr| extract_lens_parts(&expr, &root))
+        .map_err(|_| syn::Error::new(path.span(), EDIT_ERROR))?
+        .segments;
+    Ok(Edit { segments, op })
+}
+
+/// Return the parts for the given root path, which is usually just the path to the root type,
+/// but may also end with the name of a variant of that type, e.g. `Msg::Update`.
+fn root_parts(path: &TypePath) -> LensParts {
+    let segments = &path.path.segments;
+    let names_variant = path.qself.is_none()
+        && segments.len() >= 2
+        && segments
+            .iter()
+            .rev()
+            .take(2)
+            .all(|segment| segment.arguments.is_empty() && is_variant_name(&segment.ident));
+    if names_variant {
+        let mut root = path.clone();
+        let variant = root.path.segments.pop().unwrap().into_value().ident;
+        // Drop the trailing `::` left behind by `pop`
+        let last = root.path.segments.pop().unwrap().into_value();
+        root.path.segments.push(last);
+        LensParts {
+            root,
+            segments: vec![Segment::Variant(variant)],
+        }
+    } else {
+        LensParts {
+            root: path.clone(),
+            segments: vec![],
+        }
+    }
+}
+
+/// Given an expression like `Struct1.struct2_field.vec_field[i]`, recurse until we hit the root
+/// struct and then build the list of segments that are used to look up the lenses that can be
+/// passed to `compose_lens!`.  For example, the above expression would result in the following:
 /// ```text,no_run
-///    [Struct1, struct2_field, struct3_field]
+///    root: Struct1
+///    segments: [struct2_field, vec_field, [i]]
 /// ```
-fn extract_lens_parts(field_access: &ExprField) -> Result<Vec<String>, syn::Error> {
-    // Look at the parent to determine if we're at the root, or if this is a chained field access
-    let base_parts = match &*field_access.base {
-        Expr::Path(base_expr_path) => {
-            // We hit the root of the expression; extract the struct name
-            let path_segments = &base_expr_path.path.segments;
-            if path_segments.len() > 1 {
-                Err(syn::Error::new(field_access.span(), "lens!() expression must start with unqualified struct name, e.g. `Struct.outer_field.inner_field`"))
-            } else {
-                let struct_name = path_segments[0].ident.to_string();
-                Ok(vec![struct_name])
+/// (The root of the given expression is the placeholder for the given root path.)
+fn extract_lens_parts(expr: &Expr, root: &TypePath) -> Result<LensParts, syn::Error> {
+    // Split off the last segment
+    let (base, segment) = match expr {
+        Expr::Field(field_access) => (
+            &*field_access.base,
+            match &field_access.member {
+                // (A field named `each` can still be accessed as `r#each`)
+                Member::Named(ident) if ident == "each" => Segment::Each(ident.span()),
+                Member::Named(ident) if is_variant_name(ident) => Segment::Variant(ident.clone()),
+                member => Segment::Field(member.clone()),
+            },
+        ),
+        Expr::Index(index_access) => (
+            &*index_access.expr,
+            wildcard_segment(&index_access.index)
+                .unwrap_or_else(|| Segment::Index(index_access.index.clone())),
+        ),
+        Expr::Try(try_expr) => (
+            &*try_expr.expr,
+            Segment::Some(try_expr.question_token.span()),
+        ),
+        Expr::Path(_) => {
+            // A path by itself is only allowed if it names a variant, e.g. `Shape::Circle`
+            let parts = root_parts(root);
+            if parts.segments.is_empty() {
+                return Err(syn::Error::new(expr.span(), "lens!() expression must be structured like a field access, e.g. `Struct.outer_field.inner_field`"));
             }
-        }
-        Expr::Field(base_field_access) => {
-            // This is another field access; extract the base portion first
-            extract_lens_parts(&base_field_access)
+            return Ok(parts);
         }
         _ => {
-            Err(syn::Error::new(field_access.span(), "lens!() expression must be structured like a field access, e.g. `Struct.outer_field.inner_field`"))
+            return Err(syn::Error::new(expr.span(), "lens!() expression must be structured like a field access, e.g. `Struct.outer_field.inner_field`"));
         }
     };
 
-    // Append the field name
-    base_parts.and_then(|parts| {
-        if let Member::Named(field_ident) = &field_access.member {
-            let mut new_parts = parts;
-            new_parts.push(field_ident.to_string());
-            Ok(new_parts)
-        } else {
-            Err(syn::Error::new(
-                field_access.span(),
-                "lens!() only works with named fields, e.g. `Struct.outer_field.inner_field`",
-            ))
+    // Look at the base to determine if we're at the root, or if this is a chained access
+    let mut parts = match base {
+        Expr::Path(_) => {
+            // We hit the root of the expression; this is the path to the root type, which may
+            // be qualified (e.g. `crate::model::Struct1`), may have generic arguments (e.g.
+            // `Page<User>`), or may name a variant of the root type (e.g. `Msg::Update`)
+            let parts = root_parts(root);
+            if parts.segments.is_empty()
+                && !matches!(segment, Segment::Field(_) | Segment::Variant(_))
+            {
+                return Err(syn::Error::new(
+                    expr.span(),
+                    "lens!() expression must start with a field of the root type, e.g. `Struct.vec_field[0]`",
+                ));
+            }
+            parts
         }
-    })
+        _ => {
+            // This is another field access, indexing operation, or `?`; extract the base portion
+            // first
+            extract_lens_parts(base, root)?
+        }
+    };
+
+    // Append the segment
+    parts.segments.push(segment);
+    Ok(parts)
 }
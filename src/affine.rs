@@ -0,0 +1,331 @@
+//
+// Copyright (c) 2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use std::marker::PhantomData;
+
+use crate::lens::{Lens, RefLens, ValueLens};
+
+/// An affine lens offers a purely functional means to access and/or modify a field that may or
+/// may not be present in an immutable data structure (for example, a field that only exists in
+/// one variant of an enum).
+pub trait AffineLens {
+    /// The lens source type, i.e., the object that may contain the field.
+    type Source;
+
+    /// The lens target type, i.e., the field to be accessed or modified.
+    type Target;
+
+    /// Sets the target of the lens if it is present in the source; otherwise the source is left
+    /// unchanged. (This requires a mutable source reference, and as such is typically only used
+    /// internally.)
+    #[doc(hidden)]
+    fn try_mutate(&self, source: &mut Self::Source, target: Self::Target);
+
+    /// Sets the target of the lens (if it is present) and returns the new state of the source.
+    /// (This consumes the source.)
+    fn try_set(&self, source: Self::Source, target: Self::Target) -> Self::Source {
+        let mut mutable_source = source;
+        {
+            self.try_mutate(&mut mutable_source, target);
+        }
+        mutable_source
+    }
+}
+
+/// An affine lens that allows the target to be accessed and mutated by reference.
+pub trait AffineRefLens: AffineLens {
+    /// Gets a reference to the target of the lens, or `None` if the target is not present.
+    /// (This does not consume the source.)
+    fn try_get_ref<'a>(&self, source: &'a Self::Source) -> Option<&'a Self::Target>;
+
+    /// Gets a mutable reference to the target of the lens, or `None` if the target is not present.
+    /// (This requires a mutable source reference, and as such is typically only used internally.)
+    #[doc(hidden)]
+    fn try_get_mut_ref<'a>(&self, source: &'a mut Self::Source) -> Option<&'a mut Self::Target>;
+
+    /// Modifies the target of the lens (if it is present) by applying a function to the current
+    /// value.  This consumes the source.
+    fn try_modify(
+        &self,
+        source: Self::Source,
+        f: &dyn Fn(&Self::Target) -> Self::Target,
+    ) -> Self::Source {
+        let mut mutable_source = source;
+        if let Some(target) = self.try_get_mut_ref(&mut mutable_source) {
+            *target = f(target);
+        }
+        mutable_source
+    }
+}
+
+/// An affine lens that allows the target to be accessed only by cloning or copying the target value.
+pub trait AffineValueLens: AffineLens {
+    /// Gets a copy of the lens target, or `None` if the target is not present. (This does not
+    /// consume the source.)
+    fn try_get(&self, source: &Self::Source) -> Option<Self::Target>;
+}
+
+// Automatically provides implementation of `AffineLens` trait for all `Lens`, since a lens
+// is an affine lens whose target is always present.
+impl<L: Lens + ?Sized> AffineLens for L {
+    type Source = L::Source;
+    type Target = L::Target;
+
+    #[inline(always)]
+    fn try_mutate(&self, source: &mut L::Source, target: L::Target) {
+        self.mutate(source, target)
+    }
+}
+
+// Automatically provides implementation of `AffineRefLens` trait for all `RefLens`.
+impl<L: RefLens + ?Sized> AffineRefLens for L {
+    #[inline(always)]
+    fn try_get_ref<'a>(&self, source: &'a L::Source) -> Option<&'a L::Target> {
+        Some(self.get_ref(source))
+    }
+
+    #[inline(always)]
+    fn try_get_mut_ref<'a>(&self, source: &'a mut L::Source) -> Option<&'a mut L::Target> {
+        Some(self.get_mut_ref(source))
+    }
+}
+
+// Automatically provides implementation of `AffineValueLens` trait for all `ValueLens`.
+impl<L: ValueLens + ?Sized> AffineValueLens for L {
+    #[inline(always)]
+    fn try_get(&self, source: &L::Source) -> Option<L::Target> {
+        Some(self.get(source))
+    }
+}
+
+/// Returns a marker for the target type of the given affine lens.  (This is used by the `update!`
+/// macro, like `target_type`, for a path that isn't a traversal, whose lens may only have `Lens`,
+/// e.g. one that goes through accessor methods.)
+#[doc(hidden)]
+pub fn affine_target_type<L: AffineLens>(_optic: &L) -> PhantomData<L::Target> {
+    PhantomData
+}
+
+/// Wraps an affine lens that is used to edit its target.  (This is used by the `update!` macro
+/// to apply a compound assignment or a closure to the target of a path that isn't a traversal.
+/// It calls `try_edit` on a reference to the wrapper, so that `EditInPlace` is chosen when the
+/// lens has `AffineRefLens`, and otherwise `EditByValue`, which goes through the setter of the
+/// lens, e.g. for one that goes through accessor methods.)
+#[doc(hidden)]
+pub struct AffineEdit<'a, L>(pub &'a L);
+
+/// Edits the target of an affine lens (if it is present) through a mutable reference.
+#[doc(hidden)]
+pub trait EditInPlace {
+    /// The lens source type.
+    type Source;
+
+    /// The lens target type.
+    type Target;
+
+    /// Calls the given function with a mutable reference to the target, if it is present.
+    fn try_edit<F: FnOnce(&mut Self::Target)>(&self, source: &mut Self::Source, f: F);
+}
+
+impl<'a, L: AffineRefLens> EditInPlace for AffineEdit<'a, L> {
+    type Source = L::Source;
+    type Target = L::Target;
+
+    #[inline(always)]
+    fn try_edit<F: FnOnce(&mut L::Target)>(&self, source: &mut L::Source, f: F) {
+        if let Some(target) = self.0.try_get_mut_ref(source) {
+            f(target)
+        }
+    }
+}
+
+/// Edits the target of an affine lens (if it is present) by getting a copy of the target, and
+/// then setting the edited copy.
+#[doc(hidden)]
+pub trait EditByValue {
+    /// The lens source type.
+    type Source;
+
+    /// The lens target type.
+    type Target;
+
+    /// Calls the given function with a mutable reference to a copy of the target, if it is
+    /// present, and then sets the target to the edited copy.
+    fn try_edit<F: FnOnce(&mut Self::Target)>(&self, source: &mut Self::Source, f: F);
+}
+
+impl<'a, 'b, L: AffineValueLens> EditByValue for &'b AffineEdit<'a, L> {
+    type Source = L::Source;
+    type Target = L::Target;
+
+    #[inline(always)]
+    fn try_edit<F: FnOnce(&mut L::Target)>(&self, source: &mut L::Source, f: F) {
+        if let Some(mut target) = self.0.try_get(source) {
+            f(&mut target);
+            self.0.try_mutate(source, target)
+        }
+    }
+}
+
+/// Composes an `AffineLens<A, B>` with another `AffineLens<B, C>` to produce a new
+/// `AffineLens<A, C>`.  Either side may also be a plain `Lens`.
+pub fn compose_affine<LHS, RHS>(lhs: LHS, rhs: RHS) -> ComposedAffineLens<LHS, RHS>
+where
+    LHS: AffineRefLens,
+    LHS::Target: 'static,
+    RHS: AffineLens<Source = LHS::Target>,
+{
+    ComposedAffineLens { lhs, rhs }
+}
+
+/// Composes two `AffineLens`es.
+///
+/// In pseudocode:
+/// ```text,no_run
+///     compose_affine(AffineLens<A, B>, AffineLens<B, C>) -> AffineLens<A, C>
+/// ```
+pub struct ComposedAffineLens<LHS, RHS> {
+    /// The left-hand side of the composition.
+    lhs: LHS,
+
+    /// The right-hand side of the composition.
+    rhs: RHS,
+}
+
+impl<LHS, RHS> AffineLens for ComposedAffineLens<LHS, RHS>
+where
+    LHS: AffineRefLens,
+    LHS::Target: 'static,
+    RHS: AffineLens<Source = LHS::Target>,
+{
+    type Source = LHS::Source;
+    type Target = RHS::Target;
+
+    #[inline(always)]
+    fn try_mutate(&self, source: &mut LHS::Source, target: RHS::Target) {
+        if let Some(rhs_source) = self.lhs.try_get_mut_ref(source) {
+            self.rhs.try_mutate(rhs_source, target)
+        }
+    }
+}
+
+impl<LHS, RHS> AffineRefLens for ComposedAffineLens<LHS, RHS>
+where
+    LHS: AffineRefLens,
+    LHS::Target: 'static,
+    RHS: AffineRefLens<Source = LHS::Target>,
+{
+    #[inline(always)]
+    fn try_get_ref<'a>(&self, source: &'a LHS::Source) -> Option<&'a RHS::Target> {
+        self.lhs
+            .try_get_ref(source)
+            .and_then(|rhs_source| self.rhs.try_get_ref(rhs_source))
+    }
+
+    #[inline(always)]
+    fn try_get_mut_ref<'a>(&self, source: &'a mut LHS::Source) -> Option<&'a mut RHS::Target> {
+        match self.lhs.try_get_mut_ref(source) {
+            Some(rhs_source) => self.rhs.try_get_mut_ref(rhs_source),
+            None => None,
+        }
+    }
+}
+
+impl<LHS, RHS> AffineValueLens for ComposedAffineLens<LHS, RHS>
+where
+    LHS: AffineRefLens,
+    LHS::Target: 'static,
+    RHS: AffineValueLens<Source = LHS::Target>,
+{
+    #[inline(always)]
+    fn try_get(&self, source: &LHS::Source) -> Option<RHS::Target> {
+        self.lhs
+            .try_get_ref(source)
+            .and_then(|rhs_source| self.rhs.try_get(rhs_source))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pl_lens_derive::Lenses;
+
+    #[derive(Clone, Debug, PartialEq, Lenses)]
+    struct Struct1 {
+        int32: i32,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Lenses)]
+    enum Enum1 {
+        Struct1(Struct1),
+        Pair { left: Struct1, right: Struct1 },
+        Nothing,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Lenses)]
+    struct Struct2 {
+        enum1: Enum1,
+    }
+
+    #[test]
+    fn a_lens_should_be_usable_as_an_affine_lens() {
+        let s1 = Struct1 { int32: 132 };
+        assert_eq!(Struct1Int32Lens.try_get_ref(&s1), Some(&132));
+        assert_eq!(Struct1Int32Lens.try_get(&s1), Some(132));
+        assert_eq!(Struct1Int32Lens.try_set(s1, 133), Struct1 { int32: 133 });
+    }
+
+    #[test]
+    fn affine_lens_composition_should_work() {
+        let lens = compose_affine(
+            Struct2Enum1Lens,
+            compose_affine(Enum1Struct1Prism, Struct1Int32Lens),
+        );
+
+        let s2_0 = Struct2 {
+            enum1: Enum1::Struct1(Struct1 { int32: 132 }),
+        };
+        assert_eq!(lens.try_get_ref(&s2_0), Some(&132));
+        assert_eq!(lens.try_get(&s2_0), Some(132));
+
+        let s2_1 = lens.try_set(s2_0, 133);
+        assert_eq!(s2_1.enum1, Enum1::Struct1(Struct1 { int32: 133 }));
+
+        let s2_2 = lens.try_modify(s2_1, &|a| a + 1);
+        assert_eq!(s2_2.enum1, Enum1::Struct1(Struct1 { int32: 134 }));
+
+        // The lens should have no effect when the variant does not match
+        let s2_3 = Struct2 {
+            enum1: Enum1::Nothing,
+        };
+        assert_eq!(lens.try_get_ref(&s2_3), None);
+        assert_eq!(lens.try_set(s2_3.clone(), 135), s2_3);
+    }
+
+    #[test]
+    fn affine_lens_composition_should_work_with_struct_variant_fields() {
+        let lens = compose_affine(
+            Struct2Enum1Lens,
+            compose_affine(Enum1VariantPairRightLens, Struct1Int32Lens),
+        );
+
+        let s2_0 = Struct2 {
+            enum1: Enum1::Pair {
+                left: Struct1 { int32: 1 },
+                right: Struct1 { int32: 2 },
+            },
+        };
+        assert_eq!(lens.try_get(&s2_0), Some(2));
+
+        let s2_1 = lens.try_set(s2_0, 3);
+        assert_eq!(
+            s2_1.enum1,
+            Enum1::Pair {
+                left: Struct1 { int32: 1 },
+                right: Struct1 { int32: 3 },
+            }
+        );
+    }
+}
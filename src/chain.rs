@@ -0,0 +1,218 @@
+//
+// Copyright (c) 2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use crate::iso::Iso;
+use crate::lens::{Lens, RefLens, ValueLens};
+use crate::path::LensPath;
+
+/// The identity lens, whose target is the source itself.  This is the starting point for the
+/// method-chaining lens builders generated by `#[derive(Lenses)]`.
+pub struct IdentityLens<T> {
+    _marker: PhantomData<fn(T) -> T>,
+}
+
+impl<T> IdentityLens<T> {
+    /// Returns the identity lens for `T`.
+    pub fn new() -> IdentityLens<T> {
+        IdentityLens {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for IdentityLens<T> {
+    fn default() -> IdentityLens<T> {
+        IdentityLens::new()
+    }
+}
+
+impl<T> Lens for IdentityLens<T> {
+    type Source = T;
+    type Target = T;
+
+    #[inline(always)]
+    fn path(&self) -> LensPath {
+        LensPath::empty()
+    }
+
+    #[inline(always)]
+    fn mutate(&self, source: &mut T, target: T) {
+        *source = target
+    }
+}
+
+impl<T> RefLens for IdentityLens<T> {
+    #[inline(always)]
+    fn get_ref<'a>(&self, source: &'a T) -> &'a T {
+        source
+    }
+
+    #[inline(always)]
+    fn get_mut_ref<'a>(&self, source: &'a mut T) -> &'a mut T {
+        source
+    }
+}
+
+impl<T: Clone> ValueLens for IdentityLens<T> {
+    #[inline(always)]
+    fn get(&self, source: &T) -> T {
+        source.clone()
+    }
+}
+
+impl<T> Iso for IdentityLens<T> {
+    #[inline(always)]
+    fn to_target(&self, source: T) -> T {
+        source
+    }
+
+    #[inline(always)]
+    fn to_source(&self, target: T) -> T {
+        target
+    }
+}
+
+/// Implemented (by `#[derive(Lenses)]`) for types that provide a method-chaining lens builder.
+/// The builder for a given prefix lens `P` (a lens whose target is the implementing type) has a
+/// method for each field that returns the prefix composed with the lens for that field.
+///
+/// Builders hold no state (the prefix is rebuilt from its `Default` impl on each call), which is
+/// what allows a `LensChain` to dereference to the builder for its target.
+#[doc(hidden)]
+pub trait HasLensBuilder<P> {
+    /// The builder type, for example `PersonLenses<P>`.
+    type Builder: 'static;
+
+    /// Returns a reference to the builder.
+    fn builder() -> &'static Self::Builder;
+}
+
+/// A lens produced by one of the methods of a lens builder, for example the lens returned by
+/// `Person::lenses().address()`.  A `LensChain` behaves exactly like the lens that it wraps, and
+/// if the target type has its own lens builder, the chain can be extended by calling the
+/// methods of that builder, for example `Person::lenses().address().street()`.
+pub struct LensChain<L> {
+    /// The wrapped lens.
+    lens: L,
+}
+
+impl<L> LensChain<L> {
+    /// Wraps the given lens.
+    pub fn new(lens: L) -> LensChain<L> {
+        LensChain { lens }
+    }
+
+    /// Unwraps the lens.
+    pub fn into_inner(self) -> L {
+        self.lens
+    }
+}
+
+// Dereferences to the lens builder for the target type so that the chain can be extended
+impl<L: Lens> Deref for LensChain<L>
+where
+    L::Target: HasLensBuilder<L>,
+{
+    type Target = <L::Target as HasLensBuilder<L>>::Builder;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        <L::Target as HasLensBuilder<L>>::builder()
+    }
+}
+
+impl<L: Lens> Lens for LensChain<L> {
+    type Source = L::Source;
+    type Target = L::Target;
+
+    #[inline(always)]
+    fn path(&self) -> LensPath {
+        self.lens.path()
+    }
+
+    #[inline(always)]
+    fn mutate(&self, source: &mut L::Source, target: L::Target) {
+        self.lens.mutate(source, target)
+    }
+}
+
+impl<L: RefLens> RefLens for LensChain<L> {
+    #[inline(always)]
+    fn get_ref<'a>(&self, source: &'a L::Source) -> &'a L::Target {
+        self.lens.get_ref(source)
+    }
+
+    #[inline(always)]
+    fn get_mut_ref<'a>(&self, source: &'a mut L::Source) -> &'a mut L::Target {
+        self.lens.get_mut_ref(source)
+    }
+}
+
+impl<L: ValueLens> ValueLens for LensChain<L> {
+    #[inline(always)]
+    fn get(&self, source: &L::Source) -> L::Target {
+        self.lens.get(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HasLenses;
+    use pl_lens_derive::Lenses;
+
+    #[derive(Clone, Debug, PartialEq, Lenses)]
+    struct Struct1 {
+        int32: i32,
+        string: String,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Lenses)]
+    struct Struct2 {
+        int32: i32,
+        struct1: Struct1,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Lenses)]
+    struct Struct3 {
+        struct2: Struct2,
+    }
+
+    #[test]
+    fn the_identity_lens_should_work() {
+        let lens = IdentityLens::<i32>::new();
+        assert_eq!(*lens.get_ref(&132), 132);
+        assert_eq!(lens.get(&132), 132);
+        assert_eq!(lens.set(132, 133), 133);
+        assert_eq!(lens.path(), LensPath::empty());
+    }
+
+    #[test]
+    fn a_lens_builder_should_work() {
+        let lens = Struct3::lenses().struct2().struct1().string();
+
+        let s3_0 = Struct3 {
+            struct2: Struct2 {
+                int32: 232,
+                struct1: Struct1 {
+                    int32: 132,
+                    string: "hi".to_string(),
+                },
+            },
+        };
+        assert_eq!(lens.get_ref(&s3_0), "hi");
+        assert_eq!(lens.path(), LensPath::from_vec(vec![0, 1, 1]));
+
+        let s3_1 = lens.set(s3_0, "ho".to_string());
+        assert_eq!(s3_1.struct2.struct1.string, "ho".to_string());
+        assert_eq!(s3_1.struct2.struct1.int32, 132);
+
+        let s3_2 = Struct3::lenses().struct2().int32().modify(s3_1, &|a| a + 1);
+        assert_eq!(s3_2.struct2.int32, 233);
+    }
+}
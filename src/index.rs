@@ -0,0 +1,174 @@
+//
+// Copyright (c) 2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use std::marker::PhantomData;
+
+use crate::lens::{HasLenses, HasTargetLenses, Lens, RefLens, ValueLens};
+use crate::path::LensPath;
+use crate::traversal::Traversal;
+
+/// A lens over the element at a given index of a `Vec<T>`, a boxed slice, or an array.  The type
+/// parameter is the indexed type.
+///
+/// Like indexing with `source[index]`, every method of the lens panics if the index is out of
+/// bounds, which is what allows it to be a `Lens` (and to be composed with other lenses) rather
+/// than an `AffineLens`.  The index is checked each time the lens is used, not when it is created.
+pub struct IndexLens<S> {
+    /// The index of the element.
+    index: usize,
+
+    _marker: PhantomData<fn(S) -> S>,
+}
+
+impl<S> IndexLens<S> {
+    /// Returns the lens over the element at the given index of the indexed type `S`.
+    pub fn new(index: usize) -> IndexLens<S> {
+        IndexLens {
+            index,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the index of the element.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+/// Returns a `Lens` over the element at the given `index` of a `Vec<T>`.  (The lens panics if
+/// the index is out of bounds; see `IndexLens`.)
+pub fn vec_lens<T>(index: usize) -> IndexLens<Vec<T>> {
+    IndexLens::new(index)
+}
+
+/// Implemented for types whose elements can be accessed by an index (or key) of type `I`, such
+/// as `Vec<T>` with a `usize` index or `HashMap<K, V>` with a `K` key.  (This is used by the
+/// `lens!` macro to resolve the lens for an index segment, such as `[i]`, from the target type of
+/// the lens that precedes it.)
+#[doc(hidden)]
+pub trait HasIndexLens<I> {
+    /// The type of the lens over the element at a given index.
+    type IndexLens: Lens<Source = Self>;
+
+    /// Returns the lens over the element at the given index.
+    fn index_lens(index: I) -> Self::IndexLens;
+}
+
+/// Returns a lens over the element at the given index (or key) of the target of the given lens.
+/// (This is used by the `lens!` macro to resolve the lens for an index segment, such as `[i]`,
+/// from the lens that precedes it.)
+#[doc(hidden)]
+pub fn index_lens<L: Traversal, I>(_lens: &L, index: I) -> <L::Target as HasIndexLens<I>>::IndexLens
+where
+    L::Target: HasIndexLens<I>,
+{
+    <L::Target as HasIndexLens<I>>::index_lens(index)
+}
+
+// Implements the lens traits for the lens over the elements of the given indexed type.
+macro_rules! index_lens_impls {
+    { [$($generics:tt)*] $source:ty, $elem:ident } => {
+        impl<$($generics)*> Lens for IndexLens<$source> {
+            type Source = $source;
+            type Target = $elem;
+
+            #[inline(always)]
+            fn path(&self) -> LensPath {
+                LensPath::from_index(self.index)
+            }
+
+            #[inline(always)]
+            fn mutate(&self, source: &mut $source, target: $elem) {
+                source[self.index] = target
+            }
+        }
+
+        impl<$($generics)*> RefLens for IndexLens<$source> {
+            #[inline(always)]
+            fn get_ref<'a>(&self, source: &'a $source) -> &'a $elem {
+                &source[self.index]
+            }
+
+            #[inline(always)]
+            fn get_mut_ref<'a>(&self, source: &'a mut $source) -> &'a mut $elem {
+                &mut source[self.index]
+            }
+        }
+
+        impl<$($generics)*> ValueLens for IndexLens<$source>
+        where
+            $elem: Clone,
+        {
+            #[inline(always)]
+            fn get(&self, source: &$source) -> $elem {
+                source[self.index].clone()
+            }
+        }
+
+        impl<$($generics)*> HasIndexLens<usize> for $source {
+            type IndexLens = IndexLens<$source>;
+
+            #[inline(always)]
+            fn index_lens(index: usize) -> IndexLens<$source> {
+                IndexLens::new(index)
+            }
+        }
+
+        // Allows `lens!` to reach into the element (if its type has lenses)
+        impl<$($generics)*> HasTargetLenses for IndexLens<$source>
+        where
+            $elem: HasLenses,
+        {
+            type TargetLenses = <$elem as HasLenses>::Lenses;
+
+            #[inline(always)]
+            fn target_lenses(&self) -> Self::TargetLenses {
+                <$elem as HasLenses>::lenses()
+            }
+        }
+    };
+}
+
+index_lens_impls!([T] Vec<T>, T);
+index_lens_impls!([T] Box<[T]>, T);
+index_lens_impls!([T, const N: usize] [T; N], T);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lens::modify;
+
+    #[test]
+    fn a_vec_lens_should_work() {
+        let lens = vec_lens::<u32>(1);
+
+        let v0 = vec![0u32, 1, 2];
+        assert_eq!(*lens.get_ref(&v0), 1);
+        assert_eq!(lens.path(), LensPath::new(1));
+
+        let v1 = lens.set(v0, 42);
+        assert_eq!(v1, vec![0u32, 42, 2]);
+
+        let v2 = modify(&lens, v1, |a| a - 1);
+        assert_eq!(v2, vec![0u32, 41, 2]);
+    }
+
+    #[test]
+    fn an_index_lens_should_work_for_slices_and_arrays() {
+        let lens = IndexLens::<Box<[u32]>>::new(2);
+        let b0: Box<[u32]> = vec![0, 1, 2].into_boxed_slice();
+        assert_eq!(lens.get(&b0), 2);
+        assert_eq!(&*lens.set(b0, 3), &[0, 1, 3]);
+
+        let lens = IndexLens::<[u32; 3]>::new(0);
+        assert_eq!(lens.set([0, 1, 2], 5), [5, 1, 2]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn an_index_lens_should_panic_when_the_index_is_out_of_bounds() {
+        vec_lens::<u32>(3).get(&vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,158 @@
+//
+// Copyright (c) 2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use crate::lens::{Lens, ValueLens};
+use crate::path::LensPath;
+use crate::prism::Prism;
+
+/// An isomorphism is a lossless conversion between two types, for example between a newtype
+/// wrapper and the type that it wraps.  An iso is also a lens whose target is the converted
+/// source, and a prism whose target is always present.
+pub trait Iso: Lens {
+    /// Converts the source into the target. (This consumes the source.)
+    fn to_target(&self, source: Self::Source) -> Self::Target;
+
+    /// Converts the target back into the source. (This consumes the target.)
+    fn to_source(&self, target: Self::Target) -> Self::Source;
+}
+
+// Automatically provides implementation of `Prism` trait for all `Iso`.
+impl<I: Iso + ?Sized> Prism for I {
+    #[inline(always)]
+    fn review(&self, target: I::Target) -> I::Source {
+        self.to_source(target)
+    }
+}
+
+// Automatically provides implementation of `Iso` trait for all `Box<Iso>`.
+impl<I: Iso + ?Sized> Iso for Box<I> {
+    #[inline(always)]
+    fn to_target(&self, source: I::Source) -> I::Target {
+        (**self).to_target(source)
+    }
+
+    #[inline(always)]
+    fn to_source(&self, target: I::Target) -> I::Source {
+        (**self).to_source(target)
+    }
+}
+
+/// Inverts an `Iso<A, B>` to produce a new `Iso<B, A>`.
+pub fn invert<I: Iso>(iso: I) -> InvertedIso<I> {
+    InvertedIso { iso }
+}
+
+/// Inverts an `Iso`.
+///
+/// In pseudocode:
+/// ```text,no_run
+///     invert(Iso<A, B>) -> Iso<B, A>
+/// ```
+pub struct InvertedIso<I> {
+    /// The iso to be inverted.
+    iso: I,
+}
+
+impl<I: Iso> Lens for InvertedIso<I> {
+    type Source = I::Target;
+    type Target = I::Source;
+
+    #[inline(always)]
+    fn path(&self) -> LensPath {
+        self.iso.path()
+    }
+
+    #[inline(always)]
+    fn mutate(&self, source: &mut I::Target, target: I::Source) {
+        *source = self.iso.to_target(target)
+    }
+}
+
+impl<I: Iso> ValueLens for InvertedIso<I>
+where
+    I::Target: Clone,
+{
+    #[inline(always)]
+    fn get(&self, source: &I::Target) -> I::Source {
+        self.iso.to_source(source.clone())
+    }
+}
+
+impl<I: Iso> Iso for InvertedIso<I> {
+    #[inline(always)]
+    fn to_target(&self, source: I::Target) -> I::Source {
+        self.iso.to_source(source)
+    }
+
+    #[inline(always)]
+    fn to_source(&self, target: I::Source) -> I::Target {
+        self.iso.to_target(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compose, RefLens};
+    use pl_lens_derive::Lenses;
+
+    #[derive(Clone, Debug, PartialEq, Lenses)]
+    struct Email(String);
+
+    #[derive(Clone, Debug, PartialEq, Lenses)]
+    struct PrimaryEmail(Email);
+
+    #[derive(Clone, Debug, PartialEq, Lenses)]
+    struct Account {
+        email: PrimaryEmail,
+    }
+
+    #[test]
+    fn a_newtype_iso_should_work() {
+        let e0 = EmailIso.to_source("a@b.c".to_string());
+        assert_eq!(e0, Email("a@b.c".to_string()));
+        assert_eq!(EmailIso.get_ref(&e0), "a@b.c");
+        assert_eq!(
+            EmailIso.review("d@e.f".to_string()),
+            Email("d@e.f".to_string())
+        );
+
+        let e1 = EmailIso.set(e0, "g@h.i".to_string());
+        assert_eq!(EmailIso.to_target(e1), "g@h.i".to_string());
+    }
+
+    #[test]
+    fn iso_composition_should_work() {
+        let iso = compose(PrimaryEmailIso, EmailIso);
+
+        let p0 = iso.to_source("a@b.c".to_string());
+        assert_eq!(p0, PrimaryEmail(Email("a@b.c".to_string())));
+        assert_eq!(iso.get_ref(&p0), "a@b.c");
+        assert_eq!(iso.to_target(p0), "a@b.c".to_string());
+
+        let lens = compose(AccountEmailLens, iso);
+        let a0 = Account {
+            email: PrimaryEmail(Email("a@b.c".to_string())),
+        };
+        let a1 = lens.set(a0, "d@e.f".to_string());
+        assert_eq!(a1.email, PrimaryEmail(Email("d@e.f".to_string())));
+    }
+
+    #[test]
+    fn an_inverted_iso_should_work() {
+        let iso = invert(compose(PrimaryEmailIso, EmailIso));
+
+        let p0 = iso.to_target("a@b.c".to_string());
+        assert_eq!(p0, PrimaryEmail(Email("a@b.c".to_string())));
+        assert_eq!(iso.to_source(p0.clone()), "a@b.c".to_string());
+        assert_eq!(
+            iso.get(&"d@e.f".to_string()),
+            PrimaryEmail(Email("d@e.f".to_string()))
+        );
+
+        let s1 = iso.set("g@h.i".to_string(), p0);
+        assert_eq!(s1, "a@b.c".to_string());
+    }
+}
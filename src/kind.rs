@@ -0,0 +1,126 @@
+//
+// Copyright (c) 2015-2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use crate::affine::{compose_affine, AffineLens, AffineRefLens, ComposedAffineLens};
+use crate::lens::{compose, ComposedLens, Lens, RefLens};
+use crate::traversal::{compose_traversal, ComposedTraversal, Traversal};
+
+/// Marks an optic that always has a target, i.e., a `Lens`.  (The optic kinds are used by the
+/// `lens!` macro, which can't tell from the name of a segment whether it is a field, with a lens,
+/// or a variant, with an affine lens, so the kind of each segment is resolved from the type that
+/// has it, and the segments are composed according to the kinds that were found.)
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LensKind;
+
+/// Marks an optic that has at most one target, i.e., an `AffineLens`.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AffineKind;
+
+/// Marks an optic that has any number of targets, i.e., a `Traversal`.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TraversalKind;
+
+/// Implemented for each pair of optic kinds, where `Output` is the kind of an optic that is
+/// composed from optics of those kinds (e.g. a lens composed with an affine lens is an affine
+/// lens).
+#[doc(hidden)]
+pub trait JoinKind<Other> {
+    /// The kind of the composed optic.
+    type Output: Default;
+}
+
+macro_rules! join_kind_impls {
+    { $($lhs:ident + $rhs:ident => $output:ident),+ $(,)? } => {
+        $(
+            impl JoinKind<$rhs> for $lhs {
+                type Output = $output;
+            }
+        )+
+    };
+}
+
+join_kind_impls! {
+    LensKind + LensKind => LensKind,
+    LensKind + AffineKind => AffineKind,
+    LensKind + TraversalKind => TraversalKind,
+    AffineKind + LensKind => AffineKind,
+    AffineKind + AffineKind => AffineKind,
+    AffineKind + TraversalKind => TraversalKind,
+    TraversalKind + LensKind => TraversalKind,
+    TraversalKind + AffineKind => TraversalKind,
+    TraversalKind + TraversalKind => TraversalKind,
+}
+
+/// Returns the kind of an optic that is composed from optics of the given kinds.
+#[doc(hidden)]
+pub fn join_kinds<LHS: JoinKind<RHS>, RHS>(_lhs: LHS, _rhs: RHS) -> LHS::Output {
+    Default::default()
+}
+
+/// Implemented for each optic kind, where `compose_optics` composes two optics into an optic of
+/// that kind (with `compose`, `compose_affine`, or `compose_traversal`).
+#[doc(hidden)]
+pub trait ComposeOptics<LHS, RHS> {
+    /// The composed optic.
+    type Composed;
+
+    /// Composes the given optics.
+    fn compose_optics(lhs: LHS, rhs: RHS) -> Self::Composed;
+}
+
+impl<LHS, RHS> ComposeOptics<LHS, RHS> for LensKind
+where
+    LHS: RefLens,
+    LHS::Target: 'static,
+    RHS: Lens<Source = LHS::Target>,
+{
+    type Composed = ComposedLens<LHS, RHS>;
+
+    #[inline(always)]
+    fn compose_optics(lhs: LHS, rhs: RHS) -> ComposedLens<LHS, RHS> {
+        compose(lhs, rhs)
+    }
+}
+
+impl<LHS, RHS> ComposeOptics<LHS, RHS> for AffineKind
+where
+    LHS: AffineRefLens,
+    LHS::Target: 'static,
+    RHS: AffineLens<Source = LHS::Target>,
+{
+    type Composed = ComposedAffineLens<LHS, RHS>;
+
+    #[inline(always)]
+    fn compose_optics(lhs: LHS, rhs: RHS) -> ComposedAffineLens<LHS, RHS> {
+        compose_affine(lhs, rhs)
+    }
+}
+
+impl<LHS, RHS> ComposeOptics<LHS, RHS> for TraversalKind
+where
+    LHS: Traversal,
+    LHS::Target: 'static,
+    RHS: Traversal<Source = LHS::Target>,
+{
+    type Composed = ComposedTraversal<LHS, RHS>;
+
+    #[inline(always)]
+    fn compose_optics(lhs: LHS, rhs: RHS) -> ComposedTraversal<LHS, RHS> {
+        compose_traversal(lhs, rhs)
+    }
+}
+
+/// Composes the given optics into an optic of the given kind.  (This is used by the `lens!`
+/// macro, along with `join_kinds`.)
+#[doc(hidden)]
+pub fn compose_kind<K, LHS, RHS>(_kind: K, lhs: LHS, rhs: RHS) -> K::Composed
+where
+    K: ComposeOptics<LHS, RHS>,
+{
+    K::compose_optics(lhs, rhs)
+}
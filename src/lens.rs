@@ -3,9 +3,11 @@
 // All rights reserved.
 //
 
-// use std::marker::PhantomData;
+use std::marker::PhantomData;
 
+use crate::iso::Iso;
 use crate::path::LensPath;
+use crate::traversal::Traversal;
 
 /// A lens offers a purely functional means to access and/or modify a field that is
 /// nested in an immutable data structure.
@@ -22,6 +24,7 @@ pub trait Lens {
     /// Sets the target of the lens. (This requires a mutable source reference, and as such is typically
     /// only used internally.)
     #[doc(hidden)]
+    #[allow(clippy::needless_lifetimes)]
     fn mutate<'a>(&self, source: &'a mut Self::Source, target: Self::Target);
 
     /// Sets the target of the lens and returns the new state of the source. (This consumes the source.)
@@ -74,10 +77,167 @@ pub trait ValueLens: Lens {
     fn get(&self, source: &Self::Source) -> Self::Target;
 }
 
+/// Implemented by types that provide a lens for each of their fields, typically by way of
+/// `#[derive(Lenses)]`.  This is how the `lens!` macro finds the lenses for a given type, and
+/// it also allows generic code to work with the lenses of any type, for example:
+///
+/// ```text,no_run
+///     fn all_lenses<T: HasLenses>() -> T::Lenses {
+///         T::lenses()
+///     }
+/// ```
+#[diagnostic::on_unimplemented(
+    message = "type `{Self}` does not derive `Lenses`",
+    label = "`{Self}` has no lenses",
+    note = "`lens!` can only reach into the fields of types that derive `Lenses`"
+)]
+pub trait HasLenses {
+    /// The type that enumerates the available lenses for each field.
+    type Lenses;
+
+    /// Returns the available lenses for each field.
+    fn lenses() -> Self::Lenses;
+}
+
+/// Implemented for types that have a field whose name is given by the marker type `F`, typically
+/// by way of `#[derive(Lenses)]` with `#[lens(fields = "path::to::markers")]` and `#[lens(field)]`
+/// on the field (the markers can be declared with `field_markers!`).  This allows generic code to
+/// work with a field of any type that has it, for example:
+///
+/// ```text,no_run
+///     fn touch<T: HasField<updated_at, Type = u64>>(value: T, now: u64) -> T {
+///         T::lens().set(value, now)
+///     }
+/// ```
+pub trait HasField<F> {
+    /// The type of the field.
+    type Type;
+
+    /// The type of the lens for the field.  (This is usually also a `RefLens`, except for fields
+    /// whose lens goes through getter and setter methods.)
+    type Lens: Lens<Source = Self, Target = Self::Type>;
+
+    /// Returns the lens for the field.
+    fn lens() -> Self::Lens;
+}
+
+/// Implemented (by `#[derive(Lenses)]`) for lenses whose target has lenses of its own, typically
+/// because the target type implements `HasLenses`.  (This is used by the `lens!` macro to resolve
+/// the lenses for a nested struct from the lens for its field.  It is keyed on the lens rather
+/// than on the target type so that a field whose type is foreign can still be resolved through
+/// the local type that restates its fields; see `#[lens(remote = "...")]`.)
+#[doc(hidden)]
+pub trait HasTargetLenses {
+    /// The type that enumerates the available lenses for each field of the target.
+    type TargetLenses;
+
+    /// Returns the available lenses for each field of the target.
+    fn target_lenses(&self) -> Self::TargetLenses;
+}
+
+/// Returns the available lenses for each field of the target of the given lens.  (This is used
+/// by the `lens!` macro to resolve the lenses for a nested struct from the lens for its field.)
+#[doc(hidden)]
+pub fn target_lenses<L: HasTargetLenses>(lens: &L) -> L::TargetLenses {
+    lens.target_lenses()
+}
+
+/// The name of a field (or variant) as a type, which is identified by the 64-bit FNV-1a hash of
+/// the name (see `field_name_hash`).
+#[doc(hidden)]
+pub struct FieldName<const HASH: u64>;
+
+/// Returns the 64-bit FNV-1a hash of the given field (or variant) name, which identifies its
+/// `FieldName`.  (Both `#[derive(Lenses)]` and the `lens!` macro name a field as
+/// `FieldName<{ field_name_hash("name") }>`, so that they always agree on the hash.)
+#[doc(hidden)]
+pub const fn field_name_hash(name: &str) -> u64 {
+    let bytes = name.as_bytes();
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    let mut index = 0;
+    while index < bytes.len() {
+        hash = (hash ^ bytes[index] as u64).wrapping_mul(0x0100_0000_01b3);
+        index += 1;
+    }
+    hash
+}
+
+/// Implemented (by `#[derive(Lenses)]`) for the lenses of a type, once for each of its fields (or
+/// for an enum, each of its variants), where `Name` is the `FieldName` of the field, `Optic` is
+/// the optic for the field, and `Kind` is the kind of that optic (e.g. `LensKind` for a field, or
+/// `AffineKind` for a variant).  (This is used by the `lens!` macro to look up the optic for a
+/// field by name.  The `Label` is a type named after the field, and `Source` is the type that
+/// should have the field; both are chosen by the macro, so that an unknown field is reported in
+/// terms of the expression that was written, e.g. "no field `adress` on `Person`", rather than in
+/// terms of the generated lenses.)
+#[doc(hidden)]
+#[diagnostic::on_unimplemented(
+    message = "no field `{Label}` on `{Source}`",
+    label = "unknown field"
+)]
+pub trait HasFieldLens<Name, Label, Source, Optic, Kind> {
+    /// Returns the optic for the field, along with its kind.
+    fn field_lens(source: PhantomData<Source>, lenses: Self) -> (Optic, Kind);
+}
+
+/// Like `HasFieldLens` (and implemented for everything that implements it), but used by the
+/// `lens!` macro for a capitalized name, which is most likely meant to be a variant, so that an
+/// unknown name is reported as such.
+#[doc(hidden)]
+#[diagnostic::on_unimplemented(
+    message = "no variant `{Label}` on `{Source}`",
+    label = "unknown variant"
+)]
+pub trait HasVariantLens<Name, Label, Source, Optic, Kind> {
+    /// Returns the optic for the variant, along with its kind.
+    fn variant_lens(source: PhantomData<Source>, lenses: Self) -> (Optic, Kind);
+}
+
+#[diagnostic::do_not_recommend]
+impl<Name, Label, Source, Optic, Kind, L> HasVariantLens<Name, Label, Source, Optic, Kind> for L
+where
+    L: HasFieldLens<Name, Label, Source, Optic, Kind>,
+{
+    #[inline(always)]
+    fn variant_lens(source: PhantomData<Source>, lenses: L) -> (Optic, Kind) {
+        L::field_lens(source, lenses)
+    }
+}
+
+/// Returns a marker for the target type of the given optic.  (This is used by the `lens!` macro
+/// to report an unknown field in terms of the type that should have it.)
+#[doc(hidden)]
+pub fn target_type<L: Traversal>(_optic: &L) -> PhantomData<L::Target> {
+    PhantomData
+}
+
+/// Returns a marker for the type of the given value.  (This is used by the `update!` macro to
+/// resolve the lenses for the value being updated from its type.)
+#[doc(hidden)]
+pub fn source_type<S>(_source: &S) -> PhantomData<S> {
+    PhantomData
+}
+
+/// Returns the available lenses for each field of the type with the given marker.  (This is used
+/// by the `update!` macro, along with `source_type`.)
+#[doc(hidden)]
+pub fn source_lenses<S: HasLenses>(_source: PhantomData<S>) -> S::Lenses {
+    S::lenses()
+}
+
+/// Returns the given function, with its argument fixed to a reference to the type with the given
+/// marker.  (This is used by the `update!` macro, so that the argument type of a closure such as
+/// `|n| n.to_uppercase()` is known when its body is checked.)
+#[doc(hidden)]
+pub fn target_fn<T, F: FnMut(&T) -> T>(_target: PhantomData<T>, f: F) -> F {
+    f
+}
+
 /// Modifies the target of the lens by applying a function to the current value.
 /// (This lives outside the `Lens` trait to allow lenses to be object-safe but
 /// still allow for static dispatch on the given closure.)
 #[doc(hidden)]
+#[allow(clippy::needless_lifetimes)]
 pub fn mutate_with_fn<'a, L: RefLens, F>(lens: &L, source: &'a mut L::Source, f: F)
 where
     F: Fn(&L::Target) -> L::Target,
@@ -100,6 +260,41 @@ where
     mutable_source
 }
 
+/// Replaces the target of the lens with the result of applying a function to the current value,
+/// which the function takes by value.  This consumes the source.  (This is used by the
+/// `map_<field>` methods generated by `#[derive(Lenses)]`, and unlike moving the field out of
+/// the source directly, it also works for a source that implements `Drop`.)
+#[doc(hidden)]
+pub fn map_with_fn<L: RefLens, F>(lens: &L, source: L::Source, f: F) -> L::Source
+where
+    F: FnOnce(L::Target) -> L::Target,
+{
+    let mut mutable_source = source;
+    {
+        let target = lens.get_mut_ref(&mut mutable_source);
+        // While the function runs, the target is owned by the function and also still held
+        // (bitwise) by the source, so if the function panics, abort rather than let the
+        // target be dropped a second time along with the source
+        let guard = AbortOnUnwind;
+        unsafe {
+            let value = ::std::ptr::read(target);
+            ::std::ptr::write(target, f(value));
+        }
+        ::std::mem::forget(guard);
+    }
+    mutable_source
+}
+
+/// Aborts the process when dropped, which is only allowed to happen during unwinding (see
+/// `map_with_fn`).
+struct AbortOnUnwind;
+
+impl Drop for AbortOnUnwind {
+    fn drop(&mut self) {
+        ::std::process::abort()
+    }
+}
+
 // Automatically provides implementation of `Lens` trait for all `Box<Lens>`.
 impl<L: Lens + ?Sized> Lens for Box<L> {
     type Source = L::Source;
@@ -111,7 +306,7 @@ impl<L: Lens + ?Sized> Lens for Box<L> {
     }
 
     #[inline(always)]
-    fn mutate<'a>(&self, source: &'a mut L::Source, target: L::Target) {
+    fn mutate(&self, source: &mut L::Source, target: L::Target) {
         (**self).mutate(source, target)
     }
 }
@@ -137,43 +332,6 @@ impl<L: ValueLens + ?Sized> ValueLens for Box<L> {
     }
 }
 
-// /// Returns a `Lens` over a single element at the given `index` for a `Vec<T>`.
-// pub const fn vec_lens<T>(index: usize) -> impl RefLens<Source=Vec<T>, Target=T> {
-//     VecLens { index: index, _marker: PhantomData::<T> }
-// }
-
-// struct VecLens<T> {
-//     index: usize,
-//     _marker: PhantomData<T>
-// }
-
-// impl<T> Lens for VecLens<T> {
-//     type Source = Vec<T>;
-//     type Target = T;
-
-//     #[inline(always)]
-//     fn path(&self) -> LensPath {
-//         LensPath::new(self.index as u64)
-//     }
-
-//     #[inline(always)]
-//     fn mutate<'a>(&self, source: &'a mut Vec<T>, target: T) {
-//         source[self.index] = target;
-//     }
-// }
-
-// impl<T> RefLens for VecLens<T> {
-//     #[inline(always)]
-//     fn get_ref<'a>(&self, source: &'a Vec<T>) -> &'a T {
-//         source.get(self.index).unwrap()
-//     }
-
-//     #[inline(always)]
-//     fn get_mut_ref<'a>(&self, source: &'a mut Vec<T>) -> &'a mut T {
-//         source.get_mut(self.index).unwrap()
-//     }
-// }
-
 /// Composes a `Lens<A, B>` with another `Lens<B, C>` to produce a new `Lens<A, C>`.
 // TODO: Bounds are unstable in `const fn`, so we'll do without the const-ness for now
 // pub const fn compose<LHS, RHS>(lhs: LHS, rhs: RHS) -> ComposedLens<LHS, RHS>
@@ -201,6 +359,17 @@ pub struct ComposedLens<LHS, RHS> {
     rhs: RHS,
 }
 
+// A composition of stateless lenses can be built from nothing (this is used by the lens builders
+// generated by `#[derive(Lenses)]`, which rebuild their prefix lens on each call)
+impl<LHS: Default, RHS: Default> Default for ComposedLens<LHS, RHS> {
+    fn default() -> ComposedLens<LHS, RHS> {
+        ComposedLens {
+            lhs: LHS::default(),
+            rhs: RHS::default(),
+        }
+    }
+}
+
 impl<LHS, RHS> Lens for ComposedLens<LHS, RHS>
 where
     LHS: RefLens,
@@ -216,7 +385,7 @@ where
     }
 
     #[inline(always)]
-    fn mutate<'a>(&self, source: &'a mut LHS::Source, target: RHS::Target) {
+    fn mutate(&self, source: &mut LHS::Source, target: RHS::Target) {
         let rhs_source = self.lhs.get_mut_ref(source);
         self.rhs.mutate(rhs_source, target)
     }
@@ -251,6 +420,23 @@ where
     }
 }
 
+impl<LHS, RHS> Iso for ComposedLens<LHS, RHS>
+where
+    LHS: Iso + RefLens,
+    LHS::Target: 'static,
+    RHS: Iso<Source = LHS::Target>,
+{
+    #[inline(always)]
+    fn to_target(&self, source: LHS::Source) -> RHS::Target {
+        self.rhs.to_target(self.lhs.to_target(source))
+    }
+
+    #[inline(always)]
+    fn to_source(&self, target: RHS::Target) -> LHS::Source {
+        self.lhs.to_source(self.rhs.to_source(target))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -275,10 +461,10 @@ mod tests {
         struct2: Struct2,
     }
 
-    //     #[derive(Clone, Debug, PartialEq, Lenses)]
-    //     struct Struct4 {
-    //         inner_vec: Vec<Struct1>
-    //     }
+    #[derive(Clone, Debug, PartialEq, Lenses)]
+    struct Struct4 {
+        inner_vec: Vec<Struct1>,
+    }
 
     #[test]
     fn a_basic_lens_should_work() {
@@ -311,36 +497,54 @@ mod tests {
         assert_eq!(s3_3.struct2.struct1.int16, 116);
     }
 
-    //     #[test]
-    //     fn a_vec_lens_should_work() {
-    //         let lens = vec_lens::<u32>(1);
-
-    //         let v0 = vec!(0u32, 1, 2);
-    //         assert_eq!(*lens.get_ref(&v0), 1);
-
-    //         let v1 = lens.set(v0, 42);
-    //         assert_eq!(v1, vec!(0u32, 42, 2));
-
-    //         let v2 = modify(&lens, v1, |a| a - 1);
-    //         assert_eq!(v2, vec!(0u32, 41, 2));
-    //     }
-
-    //     #[test]
-    //     fn the_lens_macro_should_support_vec_indexing() {
-    //         let lens = lens!(Struct4.inner_vec[1].foo);
+    #[test]
+    fn a_value_lens_should_work_for_any_clone_target() {
+        let s3 = Struct3 {
+            int32: 332,
+            struct2: Struct2 {
+                int32: 232,
+                string: "hi".to_string(),
+                struct1: Struct1 {
+                    int32: 132,
+                    int16: 116,
+                },
+            },
+        };
+        assert_eq!(Struct3Int32Lens.get(&s3), 332);
+        assert_eq!(lens!(Struct3.struct2.string).get(&s3), "hi".to_string());
+        assert_eq!(
+            lens!(Struct3.struct2.struct1).get(&s3),
+            Struct1 {
+                int32: 132,
+                int16: 116,
+            }
+        );
+    }
 
-    //         let s0 = Struct4 { inner_vec: vec!(
-    //             Struct1 { foo: 42, bar: 73 },
-    //             Struct1 { foo: 110, bar: 210 }
-    //         )};
-    //         assert_eq!(*lens.get_ref(&s0), 110);
+    #[test]
+    fn the_lens_macro_should_support_vec_indexing() {
+        let lens = lens!(Struct4.inner_vec[1].int32);
+
+        let s0 = Struct4 {
+            inner_vec: vec![
+                Struct1 {
+                    int32: 42,
+                    int16: 73,
+                },
+                Struct1 {
+                    int32: 110,
+                    int16: 210,
+                },
+            ],
+        };
+        assert_eq!(*lens.get_ref(&s0), 110);
 
-    //         let s1 = lens.set(s0, 111);
-    //         assert_eq!(s1.inner_vec[1].foo, 111);
+        let s1 = lens.set(s0, 111);
+        assert_eq!(s1.inner_vec[1].int32, 111);
 
-    //         let s2 = modify(&lens, s1, |a| a + 1);
-    //         assert_eq!(s2.inner_vec[1].foo, 112);
-    //     }
+        let s2 = modify(&lens, s1, |a| a + 1);
+        assert_eq!(s2.inner_vec[1].int32, 112);
+    }
 
     #[test]
     fn lens_composition_should_work_with_boxed_lenses() {
@@ -377,4 +581,11 @@ mod tests {
         assert_eq!(s3_3.struct2.struct1.int32, 135);
         assert_eq!(s3_3.struct2.struct1.int16, 116);
     }
+
+    #[test]
+    fn field_name_hash_should_be_the_fnv_1a_hash_of_the_name() {
+        assert_eq!(field_name_hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(field_name_hash("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(field_name_hash("name"), field_name_hash("Name"));
+    }
 }
@@ -8,29 +8,190 @@ extern crate self as pl_lens;
 use proc_macro_hack::proc_macro_hack;
 
 // Re-export the pl-lens-derive crate
-pub use pl_lens_derive::*;
+/// Derives lenses for each field of a struct (or prisms and affine lenses for the variants of an
+/// enum).
+///
+/// A type or attribute that lenses can't be derived for is rejected with an error that points at
+/// the offending item.  For example, "`#[lens(methods)]` may only be applied to structs with named
+/// fields":
+///
+/// ```compile_fail
+/// use pl_lens::Lenses;
+///
+/// #[derive(Lenses)]
+/// #[lens(methods)]
+/// struct Pair(u32, u32);
+/// ```
+pub use pl_lens_derive::Lenses;
 
 /// This is a macro-based shorthand that allows us to write:
 ///
 /// ```text,no_run
-///   lens!(SomeStruct.foo.bar_vec[3].baz)
+///   lens!(Order.items[3].price)
 /// ```
 ///
 /// instead of:
 ///
 /// ```text,no_run
-///   compose_lens!(SomeStructFooLens, FooBarVecLens, vec_lens::<BarThing>(3), BarThingBazLens)
+///   compose_lens!(OrderItemsLens, vec_lens::<LineItem>(3), LineItemPriceLens)
+/// ```
+///
+/// where `OrderItemsLens` and `LineItemPriceLens` are generated by `#[derive(Lenses)]`, for
+/// example:
+///
+/// ```
+/// use pl_lens::{compose_lens, lens, vec_lens, Lenses, RefLens};
+///
+/// #[derive(Lenses)]
+/// struct LineItem {
+///     price: u32,
+/// }
+///
+/// #[derive(Lenses)]
+/// struct Order {
+///     items: Vec<LineItem>,
+/// }
+///
+/// let order = Order {
+///     items: (0..4).map(|price| LineItem { price }).collect(),
+/// };
+/// let composed = compose_lens!(OrderItemsLens, vec_lens::<LineItem>(3), LineItemPriceLens);
+/// assert_eq!(lens!(Order.items[3].price).get_ref(&order), composed.get_ref(&order));
+/// ```
+///
+/// An index may be any expression that evaluates to a `usize` (for example, `[i]` or `[row + 1]`),
+/// and it is evaluated once, when the lens is created.  As with indexing a `Vec` directly, the
+/// resulting lens panics if the index is out of bounds when it is used.
+///
+/// An index segment may also look up a key in a `HashMap` or `BTreeMap`, such as
+/// `lens!(Config.env["PATH"])` or `lens!(Cache.entries[&key].hits)`.  A key given by reference
+/// (or as a string literal) is borrowed by the lens, and any other key is moved into the lens.
+/// The resulting lens panics if the key is missing when it is used.
+///
+/// A `?` after a segment whose target is an `Option` focuses on the value inside it (when the
+/// option is `Some`), such as `lens!(User.profile?.avatar?.url)`.  Since the value may be absent,
+/// the result is an affine lens, which is accessed with `try_get_ref`, `try_set`, and friends.
+///
+/// A segment may also name an enum variant, such as `lens!(Shape.Circle.radius)`, and the root
+/// may also name a variant, such as `lens!(Msg::Update.payload)`.  The variant is only present
+/// when the enum is that variant, so the result is an affine lens here too.  (Whether a segment
+/// is a field or a variant is resolved from the type that has it, so a field with a capitalized
+/// name, such as `lens!(Caps.URL)`, is still a plain lens.)
+///
+/// A `[*]` (or `.each`) segment focuses on every element of a `Vec`, slice, or array, every
+/// value of a map, or the value inside an `Option`, such as `lens!(Order.items[*].price)`.  The
+/// result is a `Traversal`, which is accessed with `get_all`, `set_all`, and `modify_all`.  (A
+/// field that is actually named `each` can be accessed as `r#each`.)
+///
+/// The root may be a generic type with its type arguments, with or without a turbofish, such as
+/// `lens!(Page<User>.items)` or `lens!(Wrapper::<u32>.inner)`.
+///
+/// A misspelled field or variant is reported against the type that lacks it, and a segment that
+/// reaches into a type that doesn't derive `Lenses` is reported as such, in each case pointing at
+/// the offending segment.  For example, "no field `adress` on `Person`":
+///
+/// ```compile_fail
+/// use pl_lens::{lens, Lenses};
+///
+/// #[derive(Lenses)]
+/// struct Person {
+///     address: String,
+/// }
+///
+/// let lens = lens!(Person.adress);
+/// ```
+///
+/// or "no variant `Circel` on `Shape`":
+///
+/// ```compile_fail
+/// use pl_lens::{lens, Lenses};
+///
+/// #[derive(Lenses)]
+/// enum Shape {
+///     Circle(f64),
+/// }
+///
+/// let lens = lens!(Shape.Circel);
+/// ```
+///
+/// or "type `u8` does not derive `Lenses`":
+///
+/// ```compile_fail
+/// use pl_lens::{lens, Lenses};
+///
+/// #[derive(Lenses)]
+/// struct Person {
+///     age: u8,
+/// }
+///
+/// let lens = lens!(Person.age.value);
 /// ```
 #[proc_macro_hack]
 pub use pl_lens_macros::lens;
 
+/// Applies any number of edits to a value in place, where each edit is an assignment to a path
+/// that is written just like a `lens!` expression without the root type (which is the type of the
+/// value).  For example:
+///
+/// ```text,no_run
+///   update!(person, address.street = s, age += 1, name = |n| n.to_uppercase())
+/// ```
+///
+/// instead of:
+///
+/// ```text,no_run
+///   let person = lens!(Person.address.street).set(person, s);
+///   let person = modify(&lens!(Person.age), person, |a| a + 1);
+///   let person = modify(&lens!(Person.name), person, |n| n.to_uppercase());
+/// ```
+///
+/// The value must be a mutable place, such as a `let mut` binding.  An edit may assign a new
+/// value (`=`), apply a compound assignment operator (such as `+=` or `|=`), or assign a closure,
+/// which is called with a reference to the current value and returns the new one.  (To assign a
+/// closure as is, wrap it in parentheses.)  The right-hand side of every assignment is evaluated
+/// before any edit is applied, so each one sees the value as it was before the update, and then
+/// the edits are applied in order.  (The paths and closures are evaluated while the value is
+/// borrowed, so an index in a path or a closure can't refer to the value itself.)
+///
+/// An edit to a field with accessor methods (`#[lens(get = ..., set = ...)]`) goes through its
+/// setter, including a compound assignment or closure, which gets the current value from the
+/// getter.
+///
+/// A path through a `?` or variant segment only changes its target when it is present, and a
+/// path through a `[*]` or `.each` segment changes every target (with a clone of the right-hand
+/// side for each one).  As with `lens!`, an unknown field is reported against the offending
+/// segment of its path.
+#[proc_macro_hack]
+pub use pl_lens_macros::update;
+
 // The following is necessary to make exported macros visible.
 #[macro_use]
 mod macros;
+#[allow(unused_imports)]
 pub use self::macros::*;
 
+mod affine;
+mod chain;
+mod index;
+mod iso;
+mod kind;
 mod lens;
+mod map;
+mod option;
 mod path;
+mod prism;
+mod traversal;
+mod tuple;
 
+pub use self::affine::*;
+pub use self::chain::*;
+pub use self::index::*;
+pub use self::iso::*;
+pub use self::kind::*;
 pub use self::lens::*;
+pub use self::map::*;
+pub use self::option::*;
 pub use self::path::*;
+pub use self::prism::*;
+pub use self::traversal::*;
+pub use self::tuple::*;
@@ -13,3 +13,46 @@ macro_rules! compose_lens {
         pl_lens::compose($head, pl_lens::compose_lens!($($tail),+))
     };
 }
+
+/// Provides a shorthand for composing a series of affine lenses (any of which may also be a
+/// plain `Lens`).
+#[macro_export]
+macro_rules! compose_affine_lens {
+    { $head:expr } => {
+        $head
+    };
+    { $head:expr, $($tail:expr),+ } => {
+        pl_lens::compose_affine($head, pl_lens::compose_affine_lens!($($tail),+))
+    };
+}
+
+/// Provides a shorthand for composing a series of traversals (any of which may also be a plain
+/// `Lens` or an `AffineLens`).
+#[macro_export]
+macro_rules! compose_traversals {
+    { $head:expr } => {
+        $head
+    };
+    { $head:expr, $($tail:expr),+ } => {
+        pl_lens::compose_traversal($head, pl_lens::compose_traversals!($($tail),+))
+    };
+}
+
+/// Declares a marker type for each of the given field names, for use with `HasField`.  The
+/// markers are uninhabited enums (named exactly like the fields), so they can't be confused with
+/// values of the same name.  For example:
+///
+/// ```text,no_run
+///     pub mod fields {
+///         pl_lens::field_markers!(pub created_at, updated_at);
+///     }
+/// ```
+#[macro_export]
+macro_rules! field_markers {
+    { $vis:vis $($name:ident),+ $(,)? } => {
+        $(
+            #[allow(non_camel_case_types, dead_code)]
+            $vis enum $name {}
+        )+
+    };
+}
@@ -0,0 +1,244 @@
+//
+// Copyright (c) 2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use std::borrow::Borrow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::marker::PhantomData;
+
+use crate::index::HasIndexLens;
+use crate::lens::{HasLenses, HasTargetLenses, Lens, RefLens, ValueLens};
+use crate::path::LensPath;
+use crate::traversal::Traversal;
+
+/// A lens over the value for a given key of a `HashMap` or `BTreeMap`, which owns the key.  The
+/// first type parameter is the map type, and the second is the key type of the map.
+///
+/// Like indexing with `map[&key]`, every method of the lens panics if the map does not contain
+/// the key (in particular, setting the value does not insert a new entry), which is what allows it
+/// to be a `Lens` (and to be composed with other lenses) rather than an `AffineLens`.
+pub struct KeyLens<M, K> {
+    /// The key of the entry.
+    key: K,
+
+    _marker: PhantomData<fn(M) -> M>,
+}
+
+impl<M, K> KeyLens<M, K> {
+    /// Returns the lens over the value for the given key of the map type `M`.
+    pub fn new(key: K) -> KeyLens<M, K> {
+        KeyLens {
+            key,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the key of the entry.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+}
+
+/// A lens over the value for a given key of a `HashMap` or `BTreeMap`, which borrows the key
+/// (which may be any type that the key type of the map can be borrowed as, just like the key
+/// given to `HashMap::get`).  The lens panics if the map does not contain the key; see `KeyLens`.
+pub struct KeyRefLens<'a, M, Q: ?Sized> {
+    /// The key of the entry.
+    key: &'a Q,
+
+    _marker: PhantomData<fn(M) -> M>,
+}
+
+impl<'a, M, Q: ?Sized> KeyRefLens<'a, M, Q> {
+    /// Returns the lens over the value for the given key of the map type `M`.
+    pub fn new(key: &'a Q) -> KeyRefLens<'a, M, Q> {
+        KeyRefLens {
+            key,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the key of the entry.
+    pub fn key(&self) -> &'a Q {
+        self.key
+    }
+}
+
+/// Returns a lens over the value for the given (borrowed) key of the target of the given lens.
+/// (This is used by the `lens!` macro to resolve the lens for a key segment, such as `[&key]` or
+/// `["PATH"]`, from the lens that precedes it.)
+#[doc(hidden)]
+pub fn key_ref_lens<'a, L: Traversal, Q: ?Sized>(
+    _lens: &L,
+    key: &'a Q,
+) -> KeyRefLens<'a, L::Target, Q> {
+    KeyRefLens::new(key)
+}
+
+/// Returns the path element for the given map key, which is derived from the hash of the key so
+/// that the paths for lenses with equal keys are equal (and the paths for lenses with different
+/// keys are distinct).  This is why the key lenses for a `BTreeMap` also require `Hash` keys.
+fn key_path<Q: Hash + ?Sized>(key: &Q) -> LensPath {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    LensPath::new(hasher.finish())
+}
+
+// Implements the lens traits for the given lens (which looks up the value with `self.key`) over
+// the given map type, whose path is built from the key by the given function.
+macro_rules! key_lens_impls {
+    { [$($generics:tt)*] $lens:ty, $map:ty, $key:ty, $value:ident, $path:ident, where $($bounds:tt)* } => {
+        impl<$($generics)*> Lens for $lens
+        where
+            $($bounds)*
+        {
+            type Source = $map;
+            type Target = $value;
+
+            #[inline(always)]
+            fn path(&self) -> LensPath {
+                $path::<$key>(self.key.borrow())
+            }
+
+            #[inline(always)]
+            fn mutate(&self, source: &mut $map, target: $value) {
+                *self.get_mut_ref(source) = target
+            }
+        }
+
+        impl<$($generics)*> RefLens for $lens
+        where
+            $($bounds)*
+        {
+            #[inline(always)]
+            fn get_ref<'s>(&self, source: &'s $map) -> &'s $value {
+                source
+                    .get::<$key>(self.key.borrow())
+                    .expect("no entry found for key")
+            }
+
+            #[inline(always)]
+            fn get_mut_ref<'s>(&self, source: &'s mut $map) -> &'s mut $value {
+                source
+                    .get_mut::<$key>(self.key.borrow())
+                    .expect("no entry found for key")
+            }
+        }
+
+        impl<$($generics)*> ValueLens for $lens
+        where
+            $($bounds)*
+            $value: Clone,
+        {
+            #[inline(always)]
+            fn get(&self, source: &$map) -> $value {
+                self.get_ref(source).clone()
+            }
+        }
+
+        // Allows `lens!` to reach into the value (if its type has lenses)
+        impl<$($generics)*> HasTargetLenses for $lens
+        where
+            $($bounds)*
+            $value: HasLenses,
+        {
+            type TargetLenses = <$value as HasLenses>::Lenses;
+
+            #[inline(always)]
+            fn target_lenses(&self) -> Self::TargetLenses {
+                <$value as HasLenses>::lenses()
+            }
+        }
+    };
+}
+
+key_lens_impls!(
+    [K, V, S] KeyLens<HashMap<K, V, S>, K>, HashMap<K, V, S>, K, V, key_path,
+    where K: Hash + Eq, S: BuildHasher,
+);
+key_lens_impls!(
+    ['a, K, V, S, Q: ?Sized] KeyRefLens<'a, HashMap<K, V, S>, Q>, HashMap<K, V, S>, Q, V, key_path,
+    where K: Hash + Eq + Borrow<Q>, Q: Hash + Eq, S: BuildHasher,
+);
+key_lens_impls!(
+    [K, V] KeyLens<BTreeMap<K, V>, K>, BTreeMap<K, V>, K, V, key_path,
+    where K: Hash + Ord,
+);
+key_lens_impls!(
+    ['a, K, V, Q: ?Sized] KeyRefLens<'a, BTreeMap<K, V>, Q>, BTreeMap<K, V>, Q, V, key_path,
+    where K: Hash + Ord + Borrow<Q>, Q: Hash + Ord,
+);
+
+impl<K: Hash + Eq, V, S: BuildHasher> HasIndexLens<K> for HashMap<K, V, S> {
+    type IndexLens = KeyLens<HashMap<K, V, S>, K>;
+
+    #[inline(always)]
+    fn index_lens(key: K) -> Self::IndexLens {
+        KeyLens::new(key)
+    }
+}
+
+impl<K: Hash + Ord, V> HasIndexLens<K> for BTreeMap<K, V> {
+    type IndexLens = KeyLens<BTreeMap<K, V>, K>;
+
+    #[inline(always)]
+    fn index_lens(key: K) -> Self::IndexLens {
+        KeyLens::new(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_lenses_should_work() {
+        let mut m0 = HashMap::new();
+        m0.insert("PATH".to_string(), "/bin".to_string());
+        m0.insert("HOME".to_string(), "/root".to_string());
+
+        let lens = KeyRefLens::<HashMap<String, String>, str>::new("PATH");
+        assert_eq!(lens.get_ref(&m0), "/bin");
+
+        let m1 = lens.set(m0, "/usr/bin".to_string());
+        assert_eq!(m1["PATH"], "/usr/bin");
+
+        let lens = KeyLens::<HashMap<String, String>, String>::new("HOME".to_string());
+        assert_eq!(lens.get(&m1), "/root");
+        assert_eq!(
+            lens.path(),
+            KeyRefLens::<HashMap<String, String>, str>::new("HOME").path()
+        );
+
+        let b0: BTreeMap<u32, bool> = vec![(1, false), (2, true)].into_iter().collect();
+        let lens = KeyLens::<BTreeMap<u32, bool>, u32>::new(1);
+        assert!(lens.set(b0, true)[&1]);
+    }
+
+    #[test]
+    fn key_lenses_for_distinct_keys_should_have_distinct_paths() {
+        let lens1 = KeyLens::<BTreeMap<u32, bool>, u32>::new(1);
+        let lens3 = KeyLens::<BTreeMap<u32, bool>, u32>::new(3);
+        assert_ne!(lens1.path(), lens3.path());
+        assert_eq!(
+            lens1.path(),
+            KeyRefLens::<BTreeMap<u32, bool>, u32>::new(&1).path()
+        );
+
+        let lens = KeyRefLens::<HashMap<String, u32>, str>::new("a");
+        assert_ne!(
+            lens.path(),
+            KeyRefLens::<HashMap<String, u32>, str>::new("b").path()
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn a_key_lens_should_panic_when_the_key_is_missing() {
+        let m: HashMap<String, u32> = HashMap::new();
+        KeyRefLens::<HashMap<String, u32>, str>::new("missing").get(&m);
+    }
+}
@@ -0,0 +1,134 @@
+//
+// Copyright (c) 2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use std::marker::PhantomData;
+
+use crate::affine::{AffineLens, AffineRefLens, AffineValueLens};
+use crate::lens::{HasLenses, HasTargetLenses};
+use crate::prism::Prism;
+use crate::traversal::Traversal;
+
+/// A prism that focuses on the value inside an `Option<T>`, which is only present when the
+/// option is `Some`.
+///
+/// This is what a `?` segment in a `lens!` expression maps to, so the result of such an
+/// expression is an affine lens rather than a `Lens`, and (for example) it has no `get_ref`:
+///
+/// ```compile_fail
+/// use pl_lens::{lens, Lenses, RefLens};
+///
+/// #[derive(Lenses)]
+/// struct Profile {
+///     url: String,
+/// }
+///
+/// #[derive(Lenses)]
+/// struct User {
+///     profile: Option<Profile>,
+/// }
+///
+/// let user = User { profile: None };
+/// lens!(User.profile?.url).get_ref(&user);
+/// ```
+pub struct SomePrism<T> {
+    _marker: PhantomData<fn(T) -> T>,
+}
+
+impl<T> SomePrism<T> {
+    /// Returns the prism for `Option<T>`.
+    pub fn new() -> SomePrism<T> {
+        SomePrism {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for SomePrism<T> {
+    fn default() -> SomePrism<T> {
+        SomePrism::new()
+    }
+}
+
+/// Returns a `Prism` that focuses on the value inside an `Option<T>`.
+pub fn some<T>() -> SomePrism<T> {
+    SomePrism::new()
+}
+
+/// Returns the prism that focuses on the value inside the (optional) target of the given lens.
+/// (This is used by the `lens!` macro to resolve the prism for a `?` segment from the lens that
+/// precedes it.)
+#[doc(hidden)]
+pub fn some_prism<L, T>(_lens: &L) -> SomePrism<T>
+where
+    L: Traversal<Target = Option<T>>,
+{
+    SomePrism::new()
+}
+
+impl<T> AffineLens for SomePrism<T> {
+    type Source = Option<T>;
+    type Target = T;
+
+    #[inline(always)]
+    fn try_mutate(&self, source: &mut Option<T>, target: T) {
+        if let Some(value) = source {
+            *value = target
+        }
+    }
+}
+
+impl<T> AffineRefLens for SomePrism<T> {
+    #[inline(always)]
+    fn try_get_ref<'a>(&self, source: &'a Option<T>) -> Option<&'a T> {
+        source.as_ref()
+    }
+
+    #[inline(always)]
+    fn try_get_mut_ref<'a>(&self, source: &'a mut Option<T>) -> Option<&'a mut T> {
+        source.as_mut()
+    }
+}
+
+impl<T: Clone> AffineValueLens for SomePrism<T> {
+    #[inline(always)]
+    fn try_get(&self, source: &Option<T>) -> Option<T> {
+        source.clone()
+    }
+}
+
+impl<T> Prism for SomePrism<T> {
+    #[inline(always)]
+    fn review(&self, target: T) -> Option<T> {
+        Some(target)
+    }
+}
+
+// Allows `lens!` to reach into the value (if its type has lenses)
+impl<T: HasLenses> HasTargetLenses for SomePrism<T> {
+    type TargetLenses = T::Lenses;
+
+    #[inline(always)]
+    fn target_lenses(&self) -> T::Lenses {
+        T::lenses()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_some_prism_should_work() {
+        let prism = some::<u32>();
+        assert_eq!(prism.try_get_ref(&Some(1)), Some(&1));
+        assert_eq!(prism.try_get(&None), None);
+
+        // Setting the value has no effect when the option is `None`
+        assert_eq!(prism.try_set(Some(1), 2), Some(2));
+        assert_eq!(prism.try_set(None, 2), None);
+
+        assert_eq!(prism.review(3), Some(3));
+    }
+}
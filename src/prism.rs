@@ -0,0 +1,15 @@
+//
+// Copyright (c) 2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use crate::affine::AffineLens;
+
+/// A prism focuses on a single case of a sum type, for example one variant of an enum.  Like any
+/// affine lens, the target may or may not be present in a given source, but unlike an affine lens,
+/// a prism can always build a new source from a target.
+pub trait Prism: AffineLens {
+    /// Builds a new source from the given target (for example, by wrapping the target in the
+    /// enum variant that the prism focuses on).
+    fn review(&self, target: Self::Target) -> Self::Source;
+}
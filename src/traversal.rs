@@ -0,0 +1,244 @@
+//
+// Copyright (c) 2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
+
+use crate::affine::AffineRefLens;
+use crate::lens::{HasLenses, HasTargetLenses};
+
+/// A traversal offers a purely functional means to access and/or modify any number of targets
+/// (for example, every element of a `Vec`) in an immutable data structure at once.
+pub trait Traversal {
+    /// The traversal source type, i.e., the object containing the targets.
+    type Source;
+
+    /// The traversal target type, i.e., the type of each target to be accessed or modified.
+    type Target;
+
+    /// Calls the given function with a reference to each target of the traversal, in order.
+    fn for_each_ref<'a>(&self, source: &'a Self::Source, f: &mut dyn FnMut(&'a Self::Target));
+
+    /// Calls the given function with a mutable reference to each target of the traversal, in
+    /// order. (This requires a mutable source reference, and as such is typically only used
+    /// internally.)
+    #[doc(hidden)]
+    fn for_each_mut(&self, source: &mut Self::Source, f: &mut dyn FnMut(&mut Self::Target));
+
+    /// Gets a reference to each target of the traversal. (This does not consume the source.)
+    fn get_all<'a>(&self, source: &'a Self::Source) -> Vec<&'a Self::Target> {
+        let mut targets = Vec::new();
+        self.for_each_ref(source, &mut |target| targets.push(target));
+        targets
+    }
+
+    /// Sets every target of the traversal to (a clone of) the given value and returns the new
+    /// state of the source. (This consumes the source.)
+    fn set_all(&self, source: Self::Source, target: Self::Target) -> Self::Source
+    where
+        Self::Target: Clone,
+    {
+        let mut mutable_source = source;
+        self.for_each_mut(&mut mutable_source, &mut |t| *t = target.clone());
+        mutable_source
+    }
+
+    /// Modifies every target of the traversal by applying a function to its current value.  This
+    /// consumes the source.
+    fn modify_all(
+        &self,
+        source: Self::Source,
+        f: &dyn Fn(&Self::Target) -> Self::Target,
+    ) -> Self::Source {
+        let mut mutable_source = source;
+        self.for_each_mut(&mut mutable_source, &mut |t| *t = f(t));
+        mutable_source
+    }
+}
+
+// Automatically provides implementation of `Traversal` trait for all `AffineRefLens` (and so for
+// all `RefLens`), since an affine lens is a traversal with at most one target.
+impl<L: AffineRefLens + ?Sized> Traversal for L {
+    type Source = L::Source;
+    type Target = L::Target;
+
+    #[inline(always)]
+    fn for_each_ref<'a>(&self, source: &'a L::Source, f: &mut dyn FnMut(&'a L::Target)) {
+        if let Some(target) = self.try_get_ref(source) {
+            f(target)
+        }
+    }
+
+    #[inline(always)]
+    fn for_each_mut(&self, source: &mut L::Source, f: &mut dyn FnMut(&mut L::Target)) {
+        if let Some(target) = self.try_get_mut_ref(source) {
+            f(target)
+        }
+    }
+}
+
+/// Composes a `Traversal<A, B>` with another `Traversal<B, C>` to produce a new
+/// `Traversal<A, C>`.  Either side may also be a plain `Lens` or an `AffineLens`.
+pub fn compose_traversal<LHS, RHS>(lhs: LHS, rhs: RHS) -> ComposedTraversal<LHS, RHS>
+where
+    LHS: Traversal,
+    LHS::Target: 'static,
+    RHS: Traversal<Source = LHS::Target>,
+{
+    ComposedTraversal { lhs, rhs }
+}
+
+/// Composes two `Traversal`s.
+///
+/// In pseudocode:
+/// ```text,no_run
+///     compose_traversal(Traversal<A, B>, Traversal<B, C>) -> Traversal<A, C>
+/// ```
+pub struct ComposedTraversal<LHS, RHS> {
+    /// The left-hand side of the composition.
+    lhs: LHS,
+
+    /// The right-hand side of the composition.
+    rhs: RHS,
+}
+
+impl<LHS, RHS> Traversal for ComposedTraversal<LHS, RHS>
+where
+    LHS: Traversal,
+    LHS::Target: 'static,
+    RHS: Traversal<Source = LHS::Target>,
+{
+    type Source = LHS::Source;
+    type Target = RHS::Target;
+
+    #[inline(always)]
+    fn for_each_ref<'a>(&self, source: &'a LHS::Source, f: &mut dyn FnMut(&'a RHS::Target)) {
+        self.lhs.for_each_ref(source, &mut |rhs_source| {
+            self.rhs.for_each_ref(rhs_source, f)
+        })
+    }
+
+    #[inline(always)]
+    fn for_each_mut(&self, source: &mut LHS::Source, f: &mut dyn FnMut(&mut RHS::Target)) {
+        self.lhs.for_each_mut(source, &mut |rhs_source| {
+            self.rhs.for_each_mut(rhs_source, f)
+        })
+    }
+}
+
+/// A traversal over every element of a `Vec<T>`, a boxed slice, or an array, over every value of
+/// a `HashMap` or `BTreeMap`, or over the value inside an `Option` (if present).  The type
+/// parameter is the traversed type.
+pub struct EachTraversal<S> {
+    _marker: PhantomData<fn(S) -> S>,
+}
+
+impl<S> EachTraversal<S> {
+    /// Returns the traversal over the elements (or values) of the traversed type `S`.
+    pub fn new() -> EachTraversal<S> {
+        EachTraversal {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<S> Default for EachTraversal<S> {
+    fn default() -> EachTraversal<S> {
+        EachTraversal::new()
+    }
+}
+
+/// Returns a `Traversal` over every element of a `Vec<T>`.
+pub fn each<T>() -> EachTraversal<Vec<T>> {
+    EachTraversal::new()
+}
+
+/// Returns the traversal over the elements (or values) of the target of the given optic.  (This
+/// is used by the `lens!` macro to resolve the traversal for a `[*]` or `.each` segment from the
+/// optic that precedes it.)
+#[doc(hidden)]
+pub fn each_traversal<L: Traversal>(_optic: &L) -> EachTraversal<L::Target> {
+    EachTraversal::new()
+}
+
+// Implements the traversal traits for the traversal over the given type, whose elements are
+// visited with the given `iter` and `iter_mut` methods.
+macro_rules! each_traversal_impls {
+    { [$($generics:tt)*] $source:ty, $elem:ident, $iter:ident, $iter_mut:ident } => {
+        impl<$($generics)*> Traversal for EachTraversal<$source> {
+            type Source = $source;
+            type Target = $elem;
+
+            #[inline(always)]
+            fn for_each_ref<'a>(&self, source: &'a $source, f: &mut dyn FnMut(&'a $elem)) {
+                source.$iter().for_each(f)
+            }
+
+            #[inline(always)]
+            fn for_each_mut(&self, source: &mut $source, f: &mut dyn FnMut(&mut $elem)) {
+                source.$iter_mut().for_each(f)
+            }
+        }
+
+        // Allows `lens!` to reach into each element (if its type has lenses)
+        impl<$($generics)*> HasTargetLenses for EachTraversal<$source>
+        where
+            $elem: HasLenses,
+        {
+            type TargetLenses = <$elem as HasLenses>::Lenses;
+
+            #[inline(always)]
+            fn target_lenses(&self) -> Self::TargetLenses {
+                <$elem as HasLenses>::lenses()
+            }
+        }
+    };
+}
+
+each_traversal_impls!([T] Vec<T>, T, iter, iter_mut);
+each_traversal_impls!([T] Box<[T]>, T, iter, iter_mut);
+each_traversal_impls!([T, const N: usize] [T; N], T, iter, iter_mut);
+each_traversal_impls!([K, V, S] HashMap<K, V, S>, V, values, values_mut);
+each_traversal_impls!([K, V] BTreeMap<K, V>, V, values, values_mut);
+each_traversal_impls!([T] Option<T>, T, iter, iter_mut);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::option::some;
+
+    #[test]
+    fn the_each_traversal_should_work() {
+        let traversal = each::<u32>();
+
+        let v0 = vec![1u32, 2, 3];
+        assert_eq!(traversal.get_all(&v0), vec![&1, &2, &3]);
+
+        let v1 = traversal.modify_all(v0, &|a| a * 10);
+        assert_eq!(v1, vec![10, 20, 30]);
+        assert_eq!(traversal.set_all(v1, 0), vec![0, 0, 0]);
+
+        let m0: BTreeMap<&str, u32> = vec![("a", 1), ("b", 2)].into_iter().collect();
+        let m1 = EachTraversal::<BTreeMap<&str, u32>>::new().modify_all(m0, &|a| a + 1);
+        assert_eq!(m1["a"], 2);
+        assert_eq!(m1["b"], 3);
+
+        let traversal = EachTraversal::<Option<u32>>::new();
+        assert_eq!(traversal.get_all(&None), Vec::<&u32>::new());
+        assert_eq!(traversal.set_all(Some(1), 2), Some(2));
+    }
+
+    #[test]
+    fn traversal_composition_should_work() {
+        let traversal = compose_traversal(EachTraversal::<Vec<Option<u32>>>::new(), some::<u32>());
+
+        let v0 = vec![Some(1), None, Some(3)];
+        assert_eq!(traversal.get_all(&v0), vec![&1, &3]);
+        assert_eq!(
+            traversal.modify_all(v0, &|a| a + 1),
+            vec![Some(2), None, Some(4)]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//
+// Copyright (c) 2019 Plausible Labs Cooperative, Inc.
+// All rights reserved.
+//
+
+use std::marker::PhantomData;
+
+use crate::lens::{HasLenses, HasTargetLenses, Lens, RefLens, ValueLens};
+use crate::path::LensPath;
+
+// Declares a lens over the element at a single position of a tuple.  The type parameter is the
+// tuple type, so that the same lens type can be used for tuples of any size.
+macro_rules! tuple_lens_struct {
+    { $lens:ident, $index:tt } => {
+        #[doc = concat!("A lens over element ", stringify!($index), " of a tuple.")]
+        pub struct $lens<T> {
+            _marker: PhantomData<fn(T) -> T>,
+        }
+
+        impl<T> $lens<T> {
+            #[doc = concat!("Returns the lens over element ", stringify!($index), " of the tuple type `T`.")]
+            pub fn new() -> $lens<T> {
+                $lens {
+                    _marker: PhantomData,
+                }
+            }
+        }
+
+        impl<T> Default for $lens<T> {
+            fn default() -> $lens<T> {
+                $lens::new()
+            }
+        }
+    };
+}
+
+tuple_lens_struct!(Tuple0Lens, 0);
+tuple_lens_struct!(Tuple1Lens, 1);
+tuple_lens_struct!(Tuple2Lens, 2);
+tuple_lens_struct!(Tuple3Lens, 3);
+tuple_lens_struct!(Tuple4Lens, 4);
+tuple_lens_struct!(Tuple5Lens, 5);
+
+// Implements the lens traits for the lens over the element at the given position of tuples of the
+// given size.
+macro_rules! tuple_lens_impls {
+    { $lens:ident, $index:tt, $target:ident, ($($elem:ident),+) } => {
+        impl<$($elem),+> Lens for $lens<($($elem,)+)> {
+            type Source = ($($elem,)+);
+            type Target = $target;
+
+            #[inline(always)]
+            fn path(&self) -> LensPath {
+                LensPath::new($index)
+            }
+
+            #[inline(always)]
+            fn mutate(&self, source: &mut ($($elem,)+), target: $target) {
+                source.$index = target
+            }
+        }
+
+        impl<$($elem),+> RefLens for $lens<($($elem,)+)> {
+            #[inline(always)]
+            fn get_ref<'a>(&self, source: &'a ($($elem,)+)) -> &'a $target {
+                &source.$index
+            }
+
+            #[inline(always)]
+            fn get_mut_ref<'a>(&self, source: &'a mut ($($elem,)+)) -> &'a mut $target {
+                &mut source.$index
+            }
+        }
+
+        impl<$($elem),+> ValueLens for $lens<($($elem,)+)>
+        where
+            $target: Clone,
+        {
+            #[inline(always)]
+            fn get(&self, source: &($($elem,)+)) -> $target {
+                source.$index.clone()
+            }
+        }
+
+        // Allows `lens!` to reach into the element (if its type has lenses)
+        impl<$($elem),+> HasTargetLenses for $lens<($($elem,)+)>
+        where
+            $target: HasLenses,
+        {
+            type TargetLenses = <$target as HasLenses>::Lenses;
+
+            #[inline(always)]
+            fn target_lenses(&self) -> Self::TargetLenses {
+                <$target as HasLenses>::lenses()
+            }
+        }
+    };
+}
+
+// Implements `HasLenses` for tuples of the given size, where the lenses are themselves a tuple,
+// so that (for example) the lens for the first element of a pair is `<(A, B)>::lenses().0`.
+macro_rules! tuple_has_lenses {
+    { ($($elem:ident),+) => ($($lens:ident),+) } => {
+        impl<$($elem),+> HasLenses for ($($elem,)+) {
+            type Lenses = ($($lens<Self>,)+);
+
+            #[inline(always)]
+            fn lenses() -> Self::Lenses {
+                ($($lens::new(),)+)
+            }
+        }
+    };
+}
+
+tuple_lens_impls!(Tuple0Lens, 0, A, (A));
+tuple_has_lenses!((A) => (Tuple0Lens));
+
+tuple_lens_impls!(Tuple0Lens, 0, A, (A, B));
+tuple_lens_impls!(Tuple1Lens, 1, B, (A, B));
+tuple_has_lenses!((A, B) => (Tuple0Lens, Tuple1Lens));
+
+tuple_lens_impls!(Tuple0Lens, 0, A, (A, B, C));
+tuple_lens_impls!(Tuple1Lens, 1, B, (A, B, C));
+tuple_lens_impls!(Tuple2Lens, 2, C, (A, B, C));
+tuple_has_lenses!((A, B, C) => (Tuple0Lens, Tuple1Lens, Tuple2Lens));
+
+tuple_lens_impls!(Tuple0Lens, 0, A, (A, B, C, D));
+tuple_lens_impls!(Tuple1Lens, 1, B, (A, B, C, D));
+tuple_lens_impls!(Tuple2Lens, 2, C, (A, B, C, D));
+tuple_lens_impls!(Tuple3Lens, 3, D, (A, B, C, D));
+tuple_has_lenses!((A, B, C, D) => (Tuple0Lens, Tuple1Lens, Tuple2Lens, Tuple3Lens));
+
+tuple_lens_impls!(Tuple0Lens, 0, A, (A, B, C, D, E));
+tuple_lens_impls!(Tuple1Lens, 1, B, (A, B, C, D, E));
+tuple_lens_impls!(Tuple2Lens, 2, C, (A, B, C, D, E));
+tuple_lens_impls!(Tuple3Lens, 3, D, (A, B, C, D, E));
+tuple_lens_impls!(Tuple4Lens, 4, E, (A, B, C, D, E));
+tuple_has_lenses!((A, B, C, D, E) => (Tuple0Lens, Tuple1Lens, Tuple2Lens, Tuple3Lens, Tuple4Lens));
+
+tuple_lens_impls!(Tuple0Lens, 0, A, (A, B, C, D, E, F));
+tuple_lens_impls!(Tuple1Lens, 1, B, (A, B, C, D, E, F));
+tuple_lens_impls!(Tuple2Lens, 2, C, (A, B, C, D, E, F));
+tuple_lens_impls!(Tuple3Lens, 3, D, (A, B, C, D, E, F));
+tuple_lens_impls!(Tuple4Lens, 4, E, (A, B, C, D, E, F));
+tuple_lens_impls!(Tuple5Lens, 5, F, (A, B, C, D, E, F));
+tuple_has_lenses!((A, B, C, D, E, F) => (Tuple0Lens, Tuple1Lens, Tuple2Lens, Tuple3Lens, Tuple4Lens, Tuple5Lens));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tuple_lenses_should_work() {
+        let lens = <(u32, String, bool)>::lenses().1;
+
+        let t0 = (1, "hi".to_string(), true);
+        assert_eq!(lens.get_ref(&t0), "hi");
+        assert_eq!(lens.path(), LensPath::new(1));
+
+        let t1 = lens.set(t0, "ho".to_string());
+        assert_eq!(t1, (1, "ho".to_string(), true));
+        assert!(<(u32, String, bool)>::lenses().2.get(&t1));
+    }
+}
@@ -39,3 +39,1188 @@ fn a_simple_nested_data_structure_should_be_lensable() {
     assert_eq!(lens!(Person.name).get_ref(&p1), "Pop Zeus");
     assert_eq!(lens!(Person.address.street).get_ref(&p1), "666 Titus Ave");
 }
+
+#[derive(Lenses)]
+struct Company {
+    name: String,
+    headquarters: Address,
+}
+
+#[test]
+fn nested_lenses_should_be_resolved_from_the_field_type() {
+    use pl_lens::{lens, Lens, RefLens};
+
+    let c0 = Company {
+        name: "Plausible Labs".to_string(),
+        headquarters: Address {
+            street: "123 Needmore Rd".to_string(),
+            city: "Dayton".to_string(),
+            postcode: "99999".to_string(),
+        },
+    };
+    assert_eq!(lens!(Company.headquarters.city).get_ref(&c0), "Dayton");
+
+    let c1 = lens!(Company.headquarters.city).set(c0, "Kettering".to_string());
+    assert_eq!(lens!(Company.name).get_ref(&c1), "Plausible Labs");
+    assert_eq!(lens!(Company.headquarters.city).get_ref(&c1), "Kettering");
+}
+
+#[test]
+fn lenses_should_be_buildable_by_chaining_methods() {
+    use pl_lens::{lens, HasLenses, Lens, RefLens, ValueLens};
+
+    let c0 = Company {
+        name: "Plausible Labs".to_string(),
+        headquarters: Address {
+            street: "123 Needmore Rd".to_string(),
+            city: "Dayton".to_string(),
+            postcode: "99999".to_string(),
+        },
+    };
+    let city = Company::lenses().headquarters().city();
+    assert_eq!(city.get_ref(&c0), "Dayton");
+    assert_eq!(city.path(), lens!(Company.headquarters.city).path());
+
+    let c1 = city.set(c0, "Kettering".to_string());
+    assert_eq!(Company::lenses().name().get(&c1), "Plausible Labs");
+    assert_eq!(c1.headquarters.city, "Kettering");
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Opaque(u32);
+
+#[derive(Lenses)]
+struct Settings {
+    enabled: bool,
+    retries: usize,
+    separator: char,
+    tags: Vec<String>,
+    limit: Option<u32>,
+    timeout: std::time::Duration,
+    opaque: Opaque,
+    fallback: Option<Box<Self>>,
+}
+
+#[test]
+fn structs_with_arbitrary_leaf_field_types_should_be_lensable() {
+    use pl_lens::{lens, Lens, RefLens, ValueLens};
+    use std::time::Duration;
+
+    let s0 = Settings {
+        enabled: true,
+        retries: 3,
+        separator: ',',
+        tags: vec!["a".to_string()],
+        limit: None,
+        timeout: Duration::from_secs(5),
+        opaque: Opaque(7),
+        fallback: None,
+    };
+    assert!(lens!(Settings.enabled).get(&s0));
+    assert_eq!(lens!(Settings.retries).get(&s0), 3);
+    assert_eq!(lens!(Settings.separator).get(&s0), ',');
+    assert_eq!(lens!(Settings.tags).get_ref(&s0), &vec!["a".to_string()]);
+    assert_eq!(lens!(Settings.limit).get(&s0), None);
+    assert_eq!(lens!(Settings.timeout).get(&s0), Duration::from_secs(5));
+    assert_eq!(lens!(Settings.opaque).get(&s0), Opaque(7));
+    assert!(lens!(Settings.fallback).get_ref(&s0).is_none());
+
+    let s1 = lens!(Settings.limit).set(s0, Some(10));
+    let s2 = lens!(Settings.opaque).set(s1, Opaque(8));
+    let s3 = lens!(Settings.tags).modify(s2, &|tags| {
+        let mut tags = tags.clone();
+        tags.push("b".to_string());
+        tags
+    });
+    assert_eq!(s3.limit, Some(10));
+    assert_eq!(s3.opaque, Opaque(8));
+    assert_eq!(s3.tags, vec!["a".to_string(), "b".to_string()]);
+    assert!(s3.enabled);
+}
+
+#[derive(Clone, Debug, PartialEq, Lenses)]
+enum Shape {
+    Circle(f64),
+    Line(f64, f64),
+    Rect { width: f64, height: f64 },
+    Empty,
+}
+
+#[test]
+fn enum_variants_should_be_accessible_through_prisms() {
+    use pl_lens::{AffineLens, AffineRefLens, AffineValueLens, HasLenses, Prism};
+
+    let lenses = Shape::lenses();
+
+    // Tuple variant with a single field
+    let circle = lenses.Circle.review(1.0);
+    assert_eq!(circle, Shape::Circle(1.0));
+    assert_eq!(lenses.Circle.try_get_ref(&circle), Some(&1.0));
+    assert_eq!(lenses.Circle.try_get(&Shape::Empty), None);
+    assert_eq!(lenses.Circle.try_set(circle, 2.0), Shape::Circle(2.0));
+    assert_eq!(lenses.Circle.try_set(Shape::Empty, 2.0), Shape::Empty);
+
+    // Tuple variant with multiple fields
+    let line = lenses.Line.review((1.0, 2.0));
+    assert_eq!(line, Shape::Line(1.0, 2.0));
+    assert_eq!(lenses.Line.try_get(&line), Some((1.0, 2.0)));
+    assert_eq!(lenses.Line.try_set(line, (3.0, 4.0)), Shape::Line(3.0, 4.0));
+
+    // Unit variant
+    assert_eq!(lenses.Empty.review(()), Shape::Empty);
+    assert_eq!(lenses.Empty.try_get(&Shape::Empty), Some(()));
+    assert_eq!(lenses.Empty.try_get(&Shape::Circle(1.0)), None);
+
+    // Struct variant
+    let rect = Shape::Rect {
+        width: 2.0,
+        height: 3.0,
+    };
+    assert_eq!(lenses.Rect.width.try_get_ref(&rect), Some(&2.0));
+    assert_eq!(lenses.Rect.height.try_get(&rect), Some(3.0));
+    assert_eq!(lenses.Rect.width.try_get(&Shape::Circle(1.0)), None);
+    assert_eq!(
+        lenses.Rect.height.try_modify(rect, &|h| h * 2.0),
+        Shape::Rect {
+            width: 2.0,
+            height: 6.0,
+        }
+    );
+}
+
+#[derive(Clone, Debug, PartialEq, Lenses)]
+enum Event {
+    Created {
+        id: u32,
+        timestamp: u64,
+        name: String,
+    },
+    Renamed {
+        timestamp: u64,
+        id: u32,
+        name: String,
+        old_name: String,
+    },
+    Deleted {
+        id: u32,
+        timestamp: u64,
+    },
+}
+
+#[derive(Lenses)]
+struct EventLog {
+    last_event: Event,
+}
+
+// The lenses for this struct would clash with those for the `Event::Renamed` variant, if the
+// latter weren't named with a `Variant` infix
+#[derive(Lenses)]
+struct EventRenamed {
+    old_name: String,
+}
+
+#[test]
+fn fields_shared_by_every_enum_variant_should_be_lensable() {
+    use pl_lens::{lens, AffineValueLens, HasLenses, Lens, RefLens, ValueLens};
+
+    let e0 = Event::Renamed {
+        timestamp: 100,
+        id: 1,
+        name: "new".to_string(),
+        old_name: "old".to_string(),
+    };
+    assert_eq!(*lens!(Event.timestamp).get_ref(&e0), 100);
+    assert_eq!(lens!(Event.id).get(&e0), 1);
+
+    let e1 = lens!(Event.timestamp).set(e0, 200);
+    assert_eq!(lens!(Event.timestamp).get(&e1), 200);
+    assert_eq!(
+        Event::lenses().Renamed.old_name.try_get(&e1),
+        Some("old".to_string())
+    );
+
+    let renamed = EventRenamed {
+        old_name: "older".to_string(),
+    };
+    assert_eq!(lens!(EventRenamed.old_name).get(&renamed), "older");
+
+    let e2 = Event::Created {
+        id: 3,
+        timestamp: 400,
+        name: "created".to_string(),
+    };
+    assert_eq!(lens!(Event.id).get(&e2), 3);
+
+    let log0 = EventLog {
+        last_event: Event::Deleted {
+            id: 2,
+            timestamp: 300,
+        },
+    };
+    let log1 = lens!(EventLog.last_event.timestamp).modify(log0, &|t| t + 1);
+    assert_eq!(
+        log1.last_event,
+        Event::Deleted {
+            id: 2,
+            timestamp: 301
+        }
+    );
+}
+
+mod inventory {
+    use pl_lens::Lenses;
+
+    #[derive(Lenses)]
+    #[lens(consts)]
+    pub struct Item {
+        pub name: String,
+        pub quantity: u32,
+    }
+}
+
+fn lenses_of<T: pl_lens::HasLenses>() -> T::Lenses {
+    T::lenses()
+}
+
+#[test]
+fn lenses_should_be_resolved_through_the_has_lenses_trait() {
+    use inventory::Item;
+    use pl_lens::{lens, Lens, RefLens, ValueLens};
+
+    let i0 = Item {
+        name: "widget".to_string(),
+        quantity: 3,
+    };
+    assert_eq!(lens!(Item.quantity).get(&i0), 3);
+    assert_eq!(lenses_of::<Item>().name.get_ref(&i0), "widget");
+
+    let i1 = lenses_of::<Item>().quantity.set(i0, 4);
+    assert_eq!(lens!(Item.quantity).get(&i1), 4);
+}
+
+#[test]
+fn lenses_should_be_accessible_as_associated_constants() {
+    use inventory::Item;
+    use pl_lens::{Lens, RefLens, ValueLens};
+
+    let i0 = Item {
+        name: "widget".to_string(),
+        quantity: 3,
+    };
+    assert_eq!(Item::NAME.get_ref(&i0), "widget");
+    assert_eq!(Item::QUANTITY.get(&i0), 3);
+
+    let i1 = Item::QUANTITY.set(i0, 4);
+    assert_eq!(i1.quantity, 4);
+}
+
+#[derive(Lenses)]
+struct Label {
+    name: String,
+}
+
+impl Label {
+    const NAME: &'static str = "label";
+}
+
+#[test]
+fn associated_constants_should_only_be_declared_when_requested() {
+    use pl_lens::{lens, RefLens};
+
+    let label = Label {
+        name: "widget".to_string(),
+    };
+    assert_eq!(Label::NAME, "label");
+    assert_eq!(lens!(Label.name).get_ref(&label), "widget");
+}
+
+#[derive(Lenses)]
+#[lens(module, consts)]
+struct User {
+    name: String,
+    id: UserId,
+}
+
+#[derive(Lenses)]
+#[lens(module = "user_id_optics")]
+struct UserId(u64);
+
+#[derive(Lenses)]
+#[lens(module)]
+enum Membership {
+    Guest,
+    Member { user: User },
+}
+
+// This would collide with the generated lenses if they were not declared in a module
+#[allow(dead_code)]
+struct UserLenses;
+
+#[test]
+fn generated_items_should_be_declared_in_a_module_when_requested() {
+    use pl_lens::{lens, AffineRefLens, HasLenses, Iso, Lens, RefLens};
+
+    let u0 = User {
+        name: "Pop Zeus".to_string(),
+        id: UserId(7),
+    };
+    assert_eq!(lens!(User.name).get_ref(&u0), "Pop Zeus");
+    assert_eq!(user_lenses::UserNameLens.get_ref(&u0), "Pop Zeus");
+    assert_eq!(User::NAME.get_ref(&u0), "Pop Zeus");
+    assert_eq!(User::lenses().id().get_ref(&u0).0, 7);
+    assert_eq!(user_id_optics::UserIdIso.to_target(UserId(9)), 9);
+
+    let u1 = lens!(User.id).set(u0, UserId(8));
+    assert_eq!(u1.id.0, 8);
+
+    let m0 = Membership::Member { user: u1 };
+    let user_name = pl_lens::compose_affine(
+        membership_lenses::MembershipVariantMemberUserLens,
+        User::NAME,
+    );
+    assert_eq!(
+        user_name.try_get_ref(&m0).map(String::as_str),
+        Some("Pop Zeus")
+    );
+    assert_eq!(user_name.try_get_ref(&Membership::Guest), None);
+}
+
+// Restates the fields of `std::ops::Range<u32>`, which cannot derive `Lenses` itself
+#[derive(Lenses)]
+#[lens(remote = "std::ops::Range<u32>", consts)]
+#[allow(dead_code)]
+struct RangeDef {
+    start: u32,
+    end: u32,
+}
+
+// A second definition for a foreign type of the same name, whose lenses are named after this
+// definition rather than `Range`, so they don't clash with those of `RangeDef`
+#[derive(Lenses)]
+#[lens(remote = "std::ops::Range<u64>")]
+#[allow(dead_code)]
+struct WideRangeDef {
+    start: u64,
+    end: u64,
+}
+
+#[derive(Lenses)]
+#[lens(consts)]
+struct Selection {
+    label: String,
+    #[lens(remote = "RangeDef")]
+    range: std::ops::Range<u32>,
+}
+
+#[test]
+fn foreign_structs_should_be_lensable_through_a_remote_definition() {
+    use pl_lens::{compose, lens, HasLenses, Lens, RefLens, ValueLens};
+
+    let r0 = 3..7;
+    assert_eq!(*RangeDefStartLens.get_ref(&r0), 3);
+    assert_eq!(RangeDef::END.get(&r0), 7);
+    assert_eq!(lens!(RangeDef.end).set(r0, 8), 3..8);
+    assert_eq!(RangeDef::lenses().start().set(3..7, 4), 4..7);
+    assert_eq!(*WideRangeDefStartLens.get_ref(&(3..7u64)), 3);
+    assert_eq!(lens!(WideRangeDef.end).set(3..7, 8), 3..8u64);
+
+    let s0 = Selection {
+        label: "word".to_string(),
+        range: 3..7,
+    };
+    assert_eq!(*lens!(Selection.range.end).get_ref(&s0), 7);
+
+    let s1 = compose(Selection::RANGE, RangeDef::START).set(s0, 4);
+    assert_eq!(s1.range, 4..7);
+    assert_eq!(s1.label, "word");
+}
+
+mod fields {
+    pl_lens::field_markers!(pub name, updated_at);
+}
+
+#[derive(Lenses)]
+#[lens(fields = "crate::fields")]
+struct Document {
+    #[lens(field)]
+    name: String,
+    body: String,
+    #[lens(field)]
+    updated_at: u64,
+}
+
+// Fields that aren't marked with `#[lens(field)]` need no marker
+#[derive(Lenses)]
+#[lens(fields = "crate::fields")]
+struct Folder {
+    entries: Vec<String>,
+    #[lens(field)]
+    updated_at: u64,
+}
+
+fn touch<T: pl_lens::HasField<fields::updated_at, Type = u64>>(value: T, now: u64) -> T {
+    use pl_lens::Lens;
+
+    T::lens().set(value, now)
+}
+
+#[test]
+fn generic_code_should_be_able_to_access_fields_by_name() {
+    use pl_lens::{HasField, RefLens};
+
+    let d0 = Document {
+        name: "notes".to_string(),
+        body: String::new(),
+        updated_at: 1,
+    };
+    let d1 = touch(d0, 2);
+    assert_eq!(d1.updated_at, 2);
+    assert_eq!(
+        <Document as HasField<fields::name>>::lens().get_ref(&d1),
+        "notes"
+    );
+
+    let f0 = Folder {
+        entries: vec![],
+        updated_at: 1,
+    };
+    assert_eq!(touch(f0, 3).updated_at, 3);
+}
+
+mod server {
+    use pl_lens::Lenses;
+
+    #[derive(Debug, Lenses)]
+    #[lens(methods, consts)]
+    pub struct Server {
+        pub host: String,
+        #[lens(get = "port", set = "set_port")]
+        port: u16,
+    }
+
+    impl Server {
+        pub fn new(host: &str, port: u16) -> Server {
+            let mut server = Server {
+                host: host.to_string(),
+                port: 0,
+            };
+            server.set_port(port);
+            server
+        }
+
+        pub fn port(&self) -> u16 {
+            self.port
+        }
+
+        // Only unprivileged ports are allowed
+        pub fn set_port(&mut self, port: u16) {
+            assert!(port >= 1024, "port {} is privileged", port);
+            self.port = port
+        }
+    }
+}
+
+#[test]
+fn lenses_should_be_able_to_go_through_accessor_methods() {
+    use pl_lens::{lens, Lens, ValueLens};
+    use server::Server;
+
+    let s0 = Server::new("localhost", 8080);
+    assert_eq!(Server::PORT.get(&s0), 8080);
+    assert_eq!(lens!(Server.port).get(&s0), 8080);
+
+    let s1 = Server::PORT.set(s0, 8081);
+    assert_eq!(s1.port(), 8081);
+    assert_eq!(s1.host, "localhost");
+
+    let s2 = s1
+        .map_port(|port| port + 1)
+        .with_host("example.com".to_string());
+    assert_eq!(s2.port(), 8082);
+    assert_eq!(s2.host, "example.com");
+}
+
+#[test]
+#[should_panic(expected = "port 80 is privileged")]
+fn lenses_that_go_through_a_setter_should_uphold_its_invariants() {
+    use pl_lens::Lens;
+    use server::Server;
+
+    Server::PORT.set(Server::new("localhost", 8080), 80);
+}
+
+#[test]
+fn update_should_go_through_accessor_methods() {
+    use pl_lens::update;
+    use server::Server;
+
+    let mut s = Server::new("localhost", 8080);
+    update!(s, port = 8081, host = "example.com".to_string());
+    assert_eq!(s.port(), 8081);
+    assert_eq!(s.host, "example.com");
+
+    update!(s, port += 1);
+    assert_eq!(s.port(), 8082);
+
+    update!(s, port = |port| port * 2);
+    assert_eq!(s.port(), 16164);
+}
+
+#[test]
+#[should_panic(expected = "port 80 is privileged")]
+fn update_should_uphold_the_invariants_of_a_setter() {
+    use pl_lens::update;
+    use server::Server;
+
+    let mut s = Server::new("localhost", 8080);
+    update!(s, port = 80);
+}
+
+#[derive(Clone, Debug, PartialEq, Lenses)]
+#[lens(methods)]
+struct Venue {
+    name: String,
+    capacity: u32,
+    location: Location,
+}
+
+#[derive(Clone, Debug, PartialEq, Lenses)]
+#[lens(methods)]
+struct Location {
+    street: String,
+    city: String,
+}
+
+#[test]
+fn update_methods_should_be_generated_when_requested() {
+    let v0 = Venue {
+        name: "Hall".to_string(),
+        capacity: 100,
+        location: Location {
+            street: "123 Needmore Rd".to_string(),
+            city: "Dayton".to_string(),
+        },
+    };
+    let v1 = v0
+        .clone()
+        .with_capacity(150)
+        .map_name(|name| name + " B")
+        .map_location(|l| l.with_street("666 Titus Ave".to_string()));
+    assert_eq!(
+        v1,
+        Venue {
+            name: "Hall B".to_string(),
+            capacity: 150,
+            location: Location {
+                street: "666 Titus Ave".to_string(),
+                city: "Dayton".to_string(),
+            },
+        }
+    );
+    assert_eq!(v0.capacity, 100);
+}
+
+#[derive(Debug, Lenses)]
+#[lens(methods)]
+struct Ticket {
+    holder: String,
+    seat: u32,
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {}
+}
+
+#[test]
+fn update_methods_should_work_for_a_struct_that_implements_drop() {
+    let t0 = Ticket {
+        holder: "Alice".to_string(),
+        seat: 12,
+    };
+    let t1 = t0.map_holder(|holder| holder.to_uppercase()).with_seat(13);
+    assert_eq!(t1.holder, "ALICE");
+    assert_eq!(t1.seat, 13);
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+#[lens(methods, consts)]
+struct Token {
+    r#type: String,
+}
+
+#[test]
+fn raw_field_names_should_be_lensable() {
+    use pl_lens::{lens, HasLenses, Lens, RefLens};
+
+    let t0 = Token {
+        r#type: "ident".to_string(),
+    };
+    assert_eq!(lens!(Token.r#type).get_ref(&t0), "ident");
+    assert_eq!(Token::TYPE.get_ref(&t0), "ident");
+    assert_eq!(Token::lenses().r#type().get_ref(&t0), "ident");
+
+    let t1 = TokenTypeLens.set(t0, "literal".to_string());
+    let t2 = t1.map_type(|t| t.to_uppercase());
+    assert_eq!(t2.with_type("punct".to_string()).r#type, "punct");
+}
+
+mod model {
+    use pl_lens::Lenses;
+
+    #[derive(Lenses)]
+    pub(crate) struct Member {
+        pub(crate) name: String,
+        pub(crate) home: crate::Address,
+    }
+
+    pub(crate) type Account = Member;
+
+    pub mod views {
+        #[test]
+        fn lenses_should_be_resolved_from_a_super_relative_root() {
+            use pl_lens::{lens, RefLens};
+
+            let m = super::Member {
+                name: "Pop Zeus".to_string(),
+                home: crate::Address {
+                    street: "123 Needmore Rd".to_string(),
+                    city: "Dayton".to_string(),
+                    postcode: "99999".to_string(),
+                },
+            };
+            assert_eq!(lens!(super::Member.name).get_ref(&m), "Pop Zeus");
+            assert_eq!(lens!(super::Account.home.city).get_ref(&m), "Dayton");
+        }
+    }
+}
+
+#[test]
+fn lenses_should_be_resolved_from_qualified_and_aliased_roots() {
+    use model::Member as M;
+    use pl_lens::{lens, Lens, RefLens};
+
+    type Alias = model::Member;
+
+    let m0 = M {
+        name: "Pop Zeus".to_string(),
+        home: Address {
+            street: "123 Needmore Rd".to_string(),
+            city: "Dayton".to_string(),
+            postcode: "99999".to_string(),
+        },
+    };
+    assert_eq!(lens!(crate::model::Member.name).get_ref(&m0), "Pop Zeus");
+    assert_eq!(lens!(self::model::Account.home.city).get_ref(&m0), "Dayton");
+    assert_eq!(lens!(M.home.street).get_ref(&m0), "123 Needmore Rd");
+
+    let m1 = lens!(Alias.home.postcode).set(m0, "45409".to_string());
+    assert_eq!(m1.home.postcode, "45409");
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct Segment(Point, Point);
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct Rect {
+    origin: Point,
+    bounds: (f64, f64),
+}
+
+#[test]
+fn positional_fields_should_be_lensable() {
+    use pl_lens::{lens, Lens, LensPath, RefLens, ValueLens};
+
+    let s0 = Segment(Point { x: 1, y: 2 }, Point { x: 3, y: 4 });
+    assert_eq!(lens!(Segment.0.x).get(&s0), 1);
+    assert_eq!(lens!(Segment.1.y).get(&s0), 4);
+    assert_eq!(lens!(Segment.1.x).path(), LensPath::from_vec(vec![1, 0]));
+
+    let s1 = lens!(Segment.1).set(s0, Point { x: 5, y: 6 });
+    assert_eq!(s1, Segment(Point { x: 1, y: 2 }, Point { x: 5, y: 6 }));
+
+    let r0 = Rect {
+        origin: Point { x: 0, y: 0 },
+        bounds: (640.0, 480.0),
+    };
+    assert_eq!(*lens!(Rect.bounds.1).get_ref(&r0), 480.0);
+    let r1 = lens!(Rect.bounds.0).set(r0, 800.0);
+    assert_eq!(r1.bounds, (800.0, 480.0));
+    assert_eq!(lens!(Rect.origin.y).get(&r1), 0);
+
+    let u0 = UserId(7);
+    assert_eq!(*lens!(UserId.0).get_ref(&u0), 7);
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct Grid {
+    rows: Vec<Vec<Point>>,
+    corners: [Point; 2],
+}
+
+#[test]
+fn index_segments_should_accept_runtime_expressions() {
+    use pl_lens::{lens, Lens, LensPath, RefLens, ValueLens};
+
+    let g0 = Grid {
+        rows: vec![
+            vec![Point { x: 0, y: 0 }, Point { x: 1, y: 0 }],
+            vec![Point { x: 0, y: 1 }, Point { x: 1, y: 1 }],
+        ],
+        corners: [Point { x: 0, y: 0 }, Point { x: 1, y: 1 }],
+    };
+
+    let row = 0;
+    let i = 1;
+    assert_eq!(lens!(Grid.rows[row + 1][i].y).get(&g0), 1);
+    assert_eq!(lens!(Grid.rows[0]).get_ref(&g0).len(), 2);
+    assert_eq!(lens!(Grid.corners[i].x).get(&g0), 1);
+    assert_eq!(
+        lens!(Grid.rows[row + 1][i].y).path(),
+        LensPath::from_vec(vec![0, 1, 1, 1])
+    );
+
+    // The index is evaluated once, when the lens is created
+    let mut calls = 0;
+    let mut next_index = || {
+        calls += 1;
+        calls - 1
+    };
+    let lens = lens!(Grid.rows[next_index()][next_index()].x);
+    assert_eq!(calls, 2);
+    let g1 = lens.set(g0, 7);
+    assert_eq!(g1.rows[0][1].x, 7);
+}
+
+#[test]
+#[should_panic]
+fn index_segments_should_panic_when_the_index_is_out_of_bounds() {
+    use pl_lens::{lens, ValueLens};
+
+    let g = Grid {
+        rows: vec![],
+        corners: [Point { x: 0, y: 0 }, Point { x: 1, y: 1 }],
+    };
+    lens!(Grid.rows[3][0].x).get(&g);
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct Config {
+    env: std::collections::HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct CacheEntry {
+    hits: u32,
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct Cache {
+    entries: std::collections::BTreeMap<(u32, u32), CacheEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Version(u32, u32);
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct Releases {
+    by_version: std::collections::BTreeMap<Version, String>,
+}
+
+#[test]
+fn key_segments_should_focus_on_map_entries() {
+    use pl_lens::{lens, Lens, RefLens, ValueLens};
+
+    let c0 = Config {
+        env: vec![("PATH".to_string(), "/bin".to_string())]
+            .into_iter()
+            .collect(),
+    };
+    assert_eq!(lens!(Config.env["PATH"]).get_ref(&c0), "/bin");
+
+    // An owned key is captured by value
+    let name = "PATH".to_string();
+    let lens = lens!(Config.env[name]);
+    let c1 = lens.set(c0, "/usr/bin".to_string());
+    assert_eq!(c1.env["PATH"], "/usr/bin");
+
+    // A borrowed key is captured by reference
+    let key = (1, 2);
+    let k0 = Cache {
+        entries: vec![(key, CacheEntry { hits: 3 })].into_iter().collect(),
+    };
+    assert_eq!(lens!(Cache.entries[&key].hits).get(&k0), 3);
+    let k1 = lens!(Cache.entries[&key].hits).set(k0, 4);
+    assert_eq!(k1.entries[&key].hits, 4);
+    let k2 = lens!(Cache.entries[(1, 2)].hits).set(k1, 5);
+    assert_eq!(k2.entries[&key].hits, 5);
+
+    let r0 = Releases {
+        by_version: vec![(Version(1, 0), "first".to_string())]
+            .into_iter()
+            .collect(),
+    };
+    let version = Version(1, 0);
+    assert_eq!(lens!(Releases.by_version[&version]).get_ref(&r0), "first");
+    let r1 = lens!(Releases.by_version[Version(1, 0)]).set(r0, "initial".to_string());
+    assert_eq!(r1.by_version[&version], "initial");
+
+    // The lenses for distinct keys have distinct paths
+    assert_ne!(
+        lens!(Releases.by_version[Version(1, 0)]).path(),
+        lens!(Releases.by_version[Version(2, 0)]).path()
+    );
+}
+
+#[test]
+#[should_panic]
+fn key_segments_should_panic_when_the_key_is_missing() {
+    use pl_lens::{lens, RefLens};
+
+    let c = Config {
+        env: std::collections::HashMap::new(),
+    };
+    lens!(Config.env["HOME"]).get_ref(&c);
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct Avatar {
+    url: String,
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct Profile {
+    avatar: Option<Avatar>,
+    nicknames: Vec<String>,
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct Subscriber {
+    profile: Option<Profile>,
+}
+
+#[test]
+fn optional_segments_should_produce_affine_lenses() {
+    use pl_lens::{lens, AffineLens, AffineRefLens, AffineValueLens};
+
+    let lens = lens!(Subscriber.profile?.avatar?.url);
+
+    let s0 = Subscriber {
+        profile: Some(Profile {
+            avatar: Some(Avatar {
+                url: "a.png".to_string(),
+            }),
+            nicknames: vec!["pz".to_string()],
+        }),
+    };
+    assert_eq!(lens.try_get_ref(&s0), Some(&"a.png".to_string()));
+    assert_eq!(
+        lens!(Subscriber.profile?.nicknames[0]).try_get(&s0),
+        Some("pz".to_string())
+    );
+
+    let s1 = lens.try_set(s0, "b.png".to_string());
+    assert_eq!(lens.try_get(&s1), Some("b.png".to_string()));
+
+    // Setting has no effect when any of the options along the way is `None`
+    let s2 = lens!(Subscriber.profile?.avatar).try_set(s1, None);
+    assert_eq!(lens.try_get_ref(&s2), None);
+    assert_eq!(lens.try_set(s2.clone(), "c.png".to_string()), s2);
+
+    let s3 = Subscriber { profile: None };
+    assert_eq!(lens!(Subscriber.profile?).try_get_ref(&s3), None);
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct Drawing {
+    shape: Shape,
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+enum Msg {
+    Update { payload: Point },
+    Resize(Point),
+    Quit,
+}
+
+#[test]
+fn variant_segments_should_produce_affine_lenses() {
+    use pl_lens::{lens, AffineLens, AffineRefLens, AffineValueLens};
+
+    let rect = Shape::Rect {
+        width: 2.0,
+        height: 3.0,
+    };
+    assert_eq!(lens!(Shape.Rect.width).try_get(&rect), Some(2.0));
+    assert_eq!(lens!(Shape::Rect.height).try_get_ref(&rect), Some(&3.0));
+    assert_eq!(lens!(Shape::Circle).try_get(&rect), None);
+    assert_eq!(lens!(Shape::Circle).try_get(&Shape::Circle(1.0)), Some(1.0));
+
+    let d0 = Drawing {
+        shape: Shape::Circle(1.0),
+    };
+    let d1 = lens!(Drawing.shape.Circle).try_set(d0, 2.0);
+    assert_eq!(d1.shape, Shape::Circle(2.0));
+
+    // Setting has no effect when the enum is a different variant
+    assert_eq!(lens!(Drawing.shape.Rect.width).try_get(&d1), None);
+    assert_eq!(lens!(Drawing.shape.Rect.width).try_set(d1.clone(), 5.0), d1);
+
+    let m0 = Msg::Update {
+        payload: Point { x: 1, y: 2 },
+    };
+    let m1 = lens!(Msg::Update.payload.x).try_set(m0, 3);
+    assert_eq!(
+        lens!(Msg.Update.payload).try_get(&m1),
+        Some(Point { x: 3, y: 2 })
+    );
+    assert_eq!(lens!(Msg.Resize.y).try_get(&m1), None);
+    assert_eq!(
+        lens!(Msg.Resize.y).try_get(&Msg::Resize(Point { x: 4, y: 5 })),
+        Some(5)
+    );
+    assert_eq!(lens!(Msg::Quit).try_get(&Msg::Quit), Some(()));
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+#[allow(non_snake_case)]
+struct Caps {
+    URL: Point,
+    DPI: u32,
+}
+
+#[test]
+fn capitalized_field_segments_should_produce_lenses() {
+    use pl_lens::{lens, Lens, RefLens, ValueLens};
+
+    let c0 = Caps {
+        URL: Point { x: 1, y: 2 },
+        DPI: 96,
+    };
+    assert_eq!(*lens!(Caps.URL.x).get_ref(&c0), 1);
+    assert_eq!(lens!(Caps.DPI).get(&c0), 96);
+
+    let c1 = lens!(Caps.URL.y).set(c0, 3);
+    assert_eq!(c1.URL, Point { x: 1, y: 3 });
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct LineItem {
+    price: u32,
+    discount: Option<u32>,
+}
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct Order {
+    items: Vec<LineItem>,
+    stock: std::collections::BTreeMap<String, u32>,
+    note: Option<String>,
+}
+
+#[test]
+fn wildcard_segments_should_produce_traversals() {
+    use pl_lens::{lens, Traversal};
+
+    let o0 = Order {
+        items: vec![
+            LineItem {
+                price: 10,
+                discount: Some(1),
+            },
+            LineItem {
+                price: 20,
+                discount: None,
+            },
+        ],
+        stock: vec![("apple".to_string(), 3), ("pear".to_string(), 0)]
+            .into_iter()
+            .collect(),
+        note: None,
+    };
+
+    let prices = lens!(Order.items[*].price);
+    assert_eq!(prices.get_all(&o0), vec![&10, &20]);
+    let o1 = prices.modify_all(o0, &|p| p * 2);
+    assert_eq!(lens!(Order.items.each.price).get_all(&o1), vec![&20, &40]);
+
+    // Traversals compose with `?` and with map values
+    let discounts = lens!(Order.items[*].discount?);
+    assert_eq!(discounts.get_all(&o1), vec![&1]);
+    let o2 = discounts.set_all(o1, 5);
+    assert_eq!(o2.items[0].discount, Some(5));
+    assert_eq!(o2.items[1].discount, None);
+
+    let o3 = lens!(Order.stock[*]).modify_all(o2, &|n| n + 1);
+    assert_eq!(lens!(Order.stock.each).get_all(&o3), vec![&4, &1]);
+
+    // An `Option` has at most one element
+    assert!(lens!(Order.note[*]).get_all(&o3).is_empty());
+}
+
+#[test]
+fn wildcard_segments_should_nest() {
+    use pl_lens::{lens, Traversal};
+
+    let g0 = Grid {
+        rows: vec![vec![Point { x: 0, y: 0 }], vec![Point { x: 1, y: 1 }]],
+        corners: [Point { x: 0, y: 0 }, Point { x: 2, y: 2 }],
+    };
+    let g1 = lens!(Grid.rows[*][*].x).modify_all(g0, &|x| x + 10);
+    assert_eq!(lens!(Grid.rows[*][0].x).get_all(&g1), vec![&10, &11]);
+    assert_eq!(lens!(Grid.corners[*].y).get_all(&g1), vec![&0, &2]);
+}
+
+#[derive(Clone, Debug, Default, Lenses, PartialEq)]
+#[lens(methods, consts)]
+struct Page<T> {
+    items: Vec<T>,
+    number: u32,
+}
+
+#[derive(Clone, Debug, Default, Lenses, PartialEq)]
+struct Wrapper<T: Clone>
+where
+    T: Default,
+{
+    inner: T,
+}
+
+#[derive(Clone, Debug, Default, Lenses, PartialEq)]
+struct Tagged<T>(T);
+
+#[derive(Clone, Debug, Lenses, PartialEq)]
+struct Labeled<K, V: Clone>(K, V);
+
+#[test]
+fn generic_roots_should_be_lensable() {
+    use pl_lens::{lens, HasLenses, Iso, Lens, RefLens, ValueLens};
+
+    let p0 = Page {
+        items: vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }],
+        number: 1,
+    };
+
+    // The root may have generic arguments, with or without a turbofish
+    let lens = lens!(Page<Point>.items[1].x);
+    assert_eq!(*lens.get_ref(&p0), 3);
+    let p1 = lens.set(p0, 5);
+    assert_eq!(lens!(Page::<Point>.items[1]).get(&p1), Point { x: 5, y: 4 });
+    assert_eq!(lens!(Wrapper::<u32>.inner).get(&Wrapper { inner: 7 }), 7);
+
+    // The generated lenses have the same generic parameters as the struct
+    assert_eq!(Page::<Point>::NUMBER.get(&p1), 1);
+    let lens = Page::<Point>::lenses().items();
+    assert_eq!(lens.get_ref(&p1).len(), 2);
+    assert_eq!(p1.clone().with_number(2).number, 2);
+    let w: Wrapper<Page<u32>> = Wrapper::default();
+    let w = lens!(Wrapper<Page<u32>>.inner.number).set(w, 3);
+    assert_eq!(w.inner.number, 3);
+    let lens = Wrapper::<Page<u32>>::lenses().inner().number();
+    assert_eq!(lens.get(&w), 3);
+
+    // Generic tuple structs and newtypes are also lensable
+    assert_eq!(lens!(Tagged::<u32>.0).get(&Tagged(7)), 7);
+    let t: Tagged<Page<u32>> = Tagged::default();
+    let t = lens!(Tagged<Page<u32>>.0.number).set(t, 4);
+    assert_eq!(t.0.number, 4);
+    assert_eq!(TaggedIso::<u32>::default().to_source(8), Tagged(8));
+    let l0 = Labeled("x", 1u32);
+    assert_eq!(*lens!(Labeled<&str, u32>.0).get_ref(&l0), "x");
+    assert_eq!(lens!(Labeled::<&str, u32>.1).set(l0, 2), Labeled("x", 2));
+}
+
+#[test]
+fn update_should_apply_every_edit_in_place() {
+    use pl_lens::update;
+
+    let mut p = Person {
+        name: "Pop Zeus".to_string(),
+        age: 58,
+        address: Address {
+            street: "123 Needmore Rd".to_string(),
+            city: "Dayton".to_string(),
+            postcode: "99999".to_string(),
+        },
+    };
+    let street = "666 Titus Ave".to_string();
+    update!(
+        p,
+        address.street = street,
+        age += 1,
+        name = |n| n.to_uppercase()
+    );
+    assert_eq!(p.name, "POP ZEUS");
+    assert_eq!(p.age, 59);
+    assert_eq!(p.address.street, "666 Titus Ave");
+    assert_eq!(p.address.city, "Dayton");
+
+    // Every right-hand side is evaluated before any edit is applied
+    update!(
+        p,
+        address.city = p.address.postcode.clone(),
+        address.postcode = p.address.city.clone()
+    );
+    assert_eq!(p.address.city, "99999");
+    assert_eq!(p.address.postcode, "Dayton");
+
+    // Paths may go through indices, options, variants, and traversals
+    let mut o = Order {
+        items: vec![
+            LineItem {
+                price: 10,
+                discount: None,
+            },
+            LineItem {
+                price: 20,
+                discount: Some(1),
+            },
+        ],
+        stock: vec![("apple".to_string(), 3)].into_iter().collect(),
+        note: None,
+    };
+    let i = 1;
+    update!(
+        o,
+        items[*].price *= 2,
+        items[i].discount? += 4,
+        items[0].discount? = 9,
+        stock["apple"] -= 1,
+        note = Some("rush".to_string()),
+    );
+    assert_eq!(
+        o.items[0],
+        LineItem {
+            price: 20,
+            discount: None
+        }
+    );
+    assert_eq!(
+        o.items[1],
+        LineItem {
+            price: 40,
+            discount: Some(5)
+        }
+    );
+    assert_eq!(o.stock["apple"], 2);
+    assert_eq!(o.note, Some("rush".to_string()));
+
+    let mut d = Drawing {
+        shape: Shape::Rect {
+            width: 2.0,
+            height: 3.0,
+        },
+    };
+    update!(d, shape.Rect.width = |w| w * 2.0, shape.Circle = 1.0);
+    assert_eq!(
+        d.shape,
+        Shape::Rect {
+            width: 4.0,
+            height: 3.0
+        }
+    );
+}